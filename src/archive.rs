@@ -0,0 +1,657 @@
+//! Backing up a project's posts to disk.
+//!
+//! An archive is a directory with one subdirectory per post, named for the post's ID. Each post
+//! directory contains `post.json`, the post exactly as returned by the cohost API (including its
+//! metadata, share tree, and ask), and a subdirectory per attachment, named for the attachment's
+//! ID, containing the attachment file under its original name:
+//!
+//! ```text
+//! archive/
+//!   185838/
+//!     post.json
+//!     2b1e7477-ba13-4f7e-9547-f0e2668b92b6/
+//!       cooltext422710535227689.png
+//! ```
+//!
+//! The archive directory also holds an `export-complete` file once an export has read every page
+//! of the project, and the logs of any imports.
+//!
+//! Archives are written with [`export`] and can be posted to another project with [`import`].
+
+use crate::attachment::guess_content_type;
+use crate::post::de;
+use crate::{
+    Attachment, AttachmentId, Client, Error, IdempotencyKey, Post, PostId, Session, ValidationError,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+/// The name of the file each post's JSON is written to.
+const POST_FILE_NAME: &str = "post.json";
+
+/// The name of the file written once an export has finished, so the next export knows the
+/// archive holds every post older than the ones it finds.
+const COMPLETE_FILE_NAME: &str = "export-complete";
+
+/// Summary of an [`export`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct ExportSummary {
+    /// Posts that were written to the archive.
+    pub exported: Vec<PostId>,
+    /// The number of posts that were already in the archive.
+    pub skipped: usize,
+    /// The number of attachment files downloaded.
+    pub attachments: usize,
+}
+
+/// Exports every post on `project` to the archive directory `dir`, creating it if needed.
+///
+/// Posts already in the archive are skipped. Once an export has finished, the next one stops
+/// reading at the first page where every post (other than pinned posts) is already in the
+/// archive, so re-running an export only fetches new posts. Posts edited after they were exported
+/// are not updated.
+///
+/// A post's `post.json` is written after all of its attachments are downloaded, and an export
+/// that fails partway through leaves the archive marked as unfinished, so the next export reads
+/// every page and completes it.
+#[tracing::instrument(skip(client, dir), fields(dir = %dir.as_ref().display()))]
+pub async fn export(
+    client: &Client,
+    project: &str,
+    dir: impl AsRef<Path>,
+) -> Result<ExportSummary, Error> {
+    let dir = dir.as_ref();
+    tokio::fs::create_dir_all(dir).await?;
+
+    // Unmark the archive as complete until this export finishes, in case it doesn't.
+    let complete_path = dir.join(COMPLETE_FILE_NAME);
+    let complete = match tokio::fs::remove_file(&complete_path).await {
+        Ok(()) => true,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut summary = ExportSummary::default();
+    for page in 0.. {
+        let items = get_raw_posts_page(client, project, page).await?;
+        if items.is_empty() {
+            break;
+        }
+
+        let mut any_new = false;
+        for item in items {
+            let post: de::Post = serde_json::from_value(item.clone())?;
+            let post_dir = dir.join(post.post_id.to_string());
+            if tokio::fs::try_exists(post_dir.join(POST_FILE_NAME)).await? {
+                summary.skipped += 1;
+                continue;
+            }
+            if !post.pinned {
+                any_new = true;
+            }
+
+            tracing::info!(post_id = %post.post_id, "exporting post");
+            tokio::fs::create_dir_all(&post_dir).await?;
+            for (attachment_id, url) in attachments(&post) {
+                download_attachment(client, &post_dir, attachment_id, url).await?;
+                summary.attachments += 1;
+            }
+            tokio::fs::write(
+                post_dir.join(POST_FILE_NAME),
+                serde_json::to_vec_pretty(&item)?,
+            )
+            .await?;
+            summary.exported.push(post.post_id);
+        }
+
+        if complete && !any_new {
+            break;
+        }
+    }
+    tokio::fs::write(&complete_path, b"").await?;
+    Ok(summary)
+}
+
+async fn get_raw_posts_page(
+    client: &Client,
+    project: &str,
+    page: u64,
+) -> Result<Vec<serde_json::Value>, Error> {
+    let posts_page: crate::page::Page<serde_json::Value> = client
+        .get(&format!("project/{}/posts", project))
+        .query(&[("page", page.to_string())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(posts_page.items)
+}
+
+/// Returns the ID and URL of every attachment in a post and its share tree.
+fn attachments(post: &de::Post) -> Vec<(AttachmentId, &str)> {
+    std::iter::once(post)
+        .chain(&post.share_tree)
+        .flat_map(|post| &post.blocks)
+        .filter_map(|block| match block {
+            de::Block::Attachment { attachment } => {
+                Some((attachment.attachment_id, attachment.file_url.as_str()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the path within a post's directory an attachment is stored at.
+pub(crate) fn attachment_path(post_dir: &Path, attachment_id: AttachmentId, url: &str) -> PathBuf {
+    let file_name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .unwrap_or("file");
+    post_dir.join(attachment_id.to_string()).join(file_name)
+}
+
+async fn download_attachment(
+    client: &Client,
+    post_dir: &Path,
+    attachment_id: AttachmentId,
+    url: &str,
+) -> Result<(), Error> {
+    let path = attachment_path(post_dir, attachment_id, url);
+    if tokio::fs::try_exists(&path).await? {
+        return Ok(());
+    }
+    tracing::info!(%attachment_id, url, "downloading attachment");
+    let content = client
+        .request_url(reqwest::Method::GET, url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, content).await?;
+    Ok(())
+}
+
+/// Summary of an [`import`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct ImportSummary {
+    /// The original and new IDs of posts that were created.
+    pub imported: Vec<(PostId, PostId)>,
+    /// The number of posts that were already imported by an earlier run.
+    pub already_imported: usize,
+    /// Posts that can't be imported, because they are shares with no content of their own.
+    pub skipped: Vec<PostId>,
+    /// Attachments that were left out of the posts they belong to because cohost wouldn't accept
+    /// them, such as files of an unsupported type, with the original IDs of their posts.
+    pub skipped_attachments: Vec<(PostId, PathBuf)>,
+}
+
+/// Creates a post on `project` for every post in the archive directory `dir`, oldest first.
+///
+/// Headlines, markdown, tags, content warnings, adult content and draft flags, and attachments
+/// (with their alt text) are kept. The original publication date is noted at the end of the
+/// markdown, and the content of any ask the post responded to is quoted at the start, since asks
+/// can't be recreated. Shares with no content of their own are skipped, as are attachments cohost
+/// wouldn't accept.
+///
+/// Each post is recorded in a log file in `dir` (named `import-{project}.log`) before it's created,
+/// and again once it has been or once creating it has failed. Posts logged as created are not
+/// imported again.
+///
+/// If creating a post fails, the error is returned after it's logged. To resume the import, call
+/// this again with the same `project` and `dir`: posts that were already imported are skipped, and
+/// before creating the post that failed, the project's recent posts are checked for it (as with
+/// [`Session::create_post_idempotent`]) in case cohost created it anyway.
+#[tracing::instrument(skip(session, dir), fields(dir = %dir.as_ref().display()))]
+pub async fn import(
+    session: &Session,
+    project: &str,
+    dir: impl AsRef<Path>,
+) -> Result<ImportSummary, Error> {
+    let dir = dir.as_ref();
+    let log_path = dir.join(format!("import-{}.log", project));
+    let ImportLog { done, started } = read_import_log(&log_path).await?;
+    let mut log = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .await?;
+
+    let mut posts = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        // Skip the import logs and other files alongside the post directories.
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+        let post_file = entry.path().join(POST_FILE_NAME);
+        if tokio::fs::try_exists(&post_file).await? {
+            let post: de::Post = serde_json::from_slice(&tokio::fs::read(&post_file).await?)?;
+            posts.push((entry.path(), post));
+        }
+    }
+    posts.sort_by_key(|(_, post)| (post.published_at, post.post_id));
+
+    let mut summary = ImportSummary::default();
+    for (post_dir, post) in posts {
+        let original_id = post.post_id;
+        if done.contains_key(&original_id) {
+            summary.already_imported += 1;
+            continue;
+        }
+        let mut skipped_attachments = Vec::new();
+        let Some(mut post) = load_post(&post_dir, post, &mut skipped_attachments).await? else {
+            tracing::info!(%original_id, "skipping share with no content");
+            summary.skipped.push(original_id);
+            continue;
+        };
+        summary.skipped_attachments.extend(
+            skipped_attachments
+                .into_iter()
+                .map(|path| (original_id, path)),
+        );
+
+        // An earlier run may have stopped after creating the post, but before logging it.
+        if let Some(started_at) = started.get(&original_id) {
+            let key = IdempotencyKey::for_post(&post);
+            let found =
+                crate::idempotency::find_post(session, project, key, *started_at, Some(&post))
+                    .await?;
+            if let Some(new_id) = found {
+                tracing::info!(%original_id, %new_id, "found post created by an earlier import");
+                write_log(&mut log, format!("{} {}", original_id, new_id)).await?;
+                summary.imported.push((original_id, new_id));
+                continue;
+            }
+        }
+
+        tracing::info!(%original_id, "importing post");
+        write_log(
+            &mut log,
+            format!("{} started {}", original_id, Utc::now().to_rfc3339()),
+        )
+        .await?;
+        let new_id = match session.create_post(project, &mut post).await {
+            Ok(new_id) => new_id,
+            Err(err) => {
+                write_log(&mut log, format!("{} failed {}", original_id, err)).await?;
+                return Err(err);
+            }
+        };
+        write_log(&mut log, format!("{} {}", original_id, new_id)).await?;
+        summary.imported.push((original_id, new_id));
+    }
+    Ok(summary)
+}
+
+/// The contents of an import log: the posts that were imported, and the posts an import started
+/// creating, with when it started. Lines noting failures are only for people reading the log.
+#[derive(Debug, Default)]
+struct ImportLog {
+    done: HashMap<PostId, PostId>,
+    started: HashMap<PostId, DateTime<Utc>>,
+}
+
+async fn read_import_log(path: &Path) -> Result<ImportLog, Error> {
+    let log = match tokio::fs::read_to_string(path).await {
+        Ok(log) => log,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(ImportLog::default()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut import_log = ImportLog::default();
+    for line in log.lines() {
+        let Some((original, rest)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(original) = original.parse() else {
+            continue;
+        };
+        if let Some(started_at) = rest.strip_prefix("started ") {
+            if let Ok(started_at) = DateTime::parse_from_rfc3339(started_at) {
+                import_log
+                    .started
+                    .insert(original, started_at.with_timezone(&Utc));
+            }
+        } else if let Ok(new) = rest.parse() {
+            import_log.done.insert(original, new);
+        }
+    }
+    Ok(import_log)
+}
+
+async fn write_log(log: &mut tokio::fs::File, line: String) -> Result<(), Error> {
+    log.write_all((line + "\n").as_bytes()).await?;
+    log.flush().await?;
+    Ok(())
+}
+
+/// Builds the post to create from an archived post, or returns `None` if the post has no content
+/// of its own.
+///
+/// Attachments cohost wouldn't accept are left out, and their paths added to `skipped`.
+async fn load_post(
+    post_dir: &Path,
+    api: de::Post,
+    skipped: &mut Vec<PathBuf>,
+) -> Result<Option<Post>, Error> {
+    let mut attachments = Vec::new();
+    let mut paths = Vec::new();
+    for block in &api.blocks {
+        if let de::Block::Attachment { attachment } = block {
+            let path = attachment_path(post_dir, attachment.attachment_id, &attachment.file_url);
+            let mut new = Attachment::new_from_file(&path, guess_content_type(&path), None).await?;
+            if let Some(alt_text) = &attachment.alt_text {
+                new = new.with_alt_text(alt_text.clone());
+            }
+            attachments.push(new);
+            paths.push(path);
+        }
+    }
+    let mut loaded = Post {
+        attachments,
+        ..Post::default()
+    };
+    let rejected = loaded
+        .validate()
+        .into_iter()
+        .filter_map(|err| match err {
+            ValidationError::AttachmentTooLarge { index, .. }
+            | ValidationError::UnsupportedAttachmentType { index, .. } => Some((index, err)),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    let mut attachments = Vec::new();
+    for (index, (attachment, path)) in std::mem::take(&mut loaded.attachments)
+        .into_iter()
+        .zip(paths)
+        .enumerate()
+    {
+        match rejected.get(&index) {
+            Some(err) => {
+                tracing::warn!(path = %path.display(), %err, "skipping attachment");
+                skipped.push(path);
+            }
+            None => attachments.push(attachment),
+        }
+    }
+
+    let mut post = Post::from(api);
+    if post.is_empty() {
+        return Ok(None);
+    }
+    let publication_date = post
+        .metadata
+        .take()
+        .map(|metadata| metadata.publication_date);
+
+    let mut markdown = Vec::new();
+    if let Some(ask) = post.ask.take() {
+        let asker = ask.asker.map_or_else(
+            || "Anonymous".to_owned(),
+            |asker| format!("@{}", asker.handle),
+        );
+        let quoted = ask
+            .content
+            .lines()
+            .map(|line| format!("> {}", line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        markdown.push(format!("> **{} asked:**\n>\n{}", asker, quoted));
+    }
+    if !post.markdown.is_empty() {
+        markdown.push(std::mem::take(&mut post.markdown));
+    }
+    if let Some(date) = publication_date {
+        markdown.push(format!(
+            "<small>Originally posted {}.</small>",
+            date.format("%B %-d, %Y")
+        ));
+    }
+    post.markdown = markdown.join("\n\n");
+    post.attachments = attachments;
+    Ok(Some(post))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        attachment_path, attachments, export, import, load_post, read_import_log,
+        COMPLETE_FILE_NAME, POST_FILE_NAME,
+    };
+    use crate::test_util::{posts_page, session};
+    use crate::{AttachmentId, Client, MemoryTransport, PostId};
+    use chrono::Utc;
+    use serde_json::json;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn test_load_post() -> Result<(), Box<dyn std::error::Error>> {
+        let post_page: crate::post::PostPage =
+            serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+        let post_dir = crate::persist::temp_path("load-post");
+        let attachment_dir = post_dir.join("2b1e7477-ba13-4f7e-9547-f0e2668b92b6");
+        tokio::fs::create_dir_all(&attachment_dir).await?;
+        tokio::fs::write(attachment_dir.join("cooltext422710535227689.png"), b"png").await?;
+
+        let api = post_page
+            .items
+            .iter()
+            .find(|post| post.post_id.0 == 185_838)
+            .expect("Couldn't find post by ID 185838 as expected; did you change the sample?");
+        let mut skipped = Vec::new();
+        let post = load_post(&post_dir, api.clone(), &mut skipped)
+            .await?
+            .expect("test post has content");
+        assert!(skipped.is_empty());
+        assert_eq!(post.headline, "This is a test post.");
+        assert_eq!(post.tags.len(), 3);
+        assert!(post.metadata.is_none());
+        assert!(post
+            .markdown
+            .ends_with("<small>Originally posted November 4, 2022.</small>"));
+        assert_eq!(post.attachments.len(), 1);
+        assert!(post.attachments[0].is_new());
+        assert_eq!(
+            post.attachments[0].alt_text.as_deref(),
+            Some("Stylized text with stars reading: \"this block is an image attachment\"")
+        );
+
+        tokio::fs::remove_dir_all(&post_dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_resumes() -> Result<(), Box<dyn std::error::Error>> {
+        let mut page: serde_json::Value =
+            serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+        let items = page["items"].as_array_mut().unwrap();
+        // Keep the two posts without attachments, one per page.
+        let (newer, older) = (items[0].take(), items[1].take());
+        let page_of =
+            |item: &serde_json::Value| json!({ "nItems": 2, "nPages": 2, "items": [item] });
+        let empty = json!({ "nItems": 2, "nPages": 2, "items": [] });
+
+        let dir = crate::persist::temp_path("export-resumes");
+        let transport = MemoryTransport::new();
+        let client = Client::new().with_transport(transport.clone());
+        let path = "project/example/posts";
+
+        // The first export fails after writing the first page.
+        transport.respond_once("GET", path, 200, page_of(&newer).to_string());
+        transport.respond_once("GET", path, 500, "");
+        assert!(export(&client, "example", &dir).await.is_err());
+        assert!(!dir.join(COMPLETE_FILE_NAME).exists());
+
+        // The next export reads past the first page, which is already archived.
+        transport.respond_once("GET", path, 200, page_of(&newer).to_string());
+        transport.respond_once("GET", path, 200, page_of(&older).to_string());
+        transport.respond_once("GET", path, 200, empty.to_string());
+        let summary = export(&client, "example", &dir).await?;
+        assert_eq!(summary.exported, [PostId(185_916)]);
+        assert_eq!(summary.skipped, 1);
+        assert!(dir.join("185916").join(POST_FILE_NAME).exists());
+        assert!(dir.join(COMPLETE_FILE_NAME).exists());
+
+        // Once the archive is complete, exports stop at the first page with nothing new.
+        let before = transport.requests().len();
+        transport.respond_once("GET", path, 200, page_of(&newer).to_string());
+        let summary = export(&client, "example", &dir).await?;
+        assert!(summary.exported.is_empty());
+        assert_eq!(transport.requests().len(), before + 1);
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_resumes() -> Result<(), Box<dyn std::error::Error>> {
+        let sample: serde_json::Value =
+            serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+        let dir = crate::persist::temp_path("import-resumes");
+        tokio::fs::create_dir_all(dir.join("185922")).await?;
+        tokio::fs::create_dir_all(dir.join("185838")).await?;
+
+        // The first post was created by an earlier run, which stopped before logging it.
+        let created = sample["items"][0].clone();
+        tokio::fs::write(dir.join("185922").join(POST_FILE_NAME), created.to_string()).await?;
+        tokio::fs::write(
+            dir.join("import-eggbug.log"),
+            format!("185922 started {}\n", Utc::now().to_rfc3339()),
+        )
+        .await?;
+        let mut skipped = Vec::new();
+        let imported = load_post(
+            &dir.join("185922"),
+            serde_json::from_value(created.clone())?,
+            &mut skipped,
+        )
+        .await?
+        .unwrap();
+        let page = posts_page(1, &imported);
+
+        // The second post has an attachment cohost won't accept.
+        let mut unsupported = sample["items"][2].clone();
+        let attachment_id = unsupported["blocks"][0]["attachment"]["attachmentId"].clone();
+        unsupported["blocks"][0]["attachment"]["fileURL"] =
+            json!("https://staging.cohostcdn.org/attachment/notes.txt");
+        tokio::fs::write(
+            dir.join("185838").join(POST_FILE_NAME),
+            unsupported.to_string(),
+        )
+        .await?;
+        let text_path = dir
+            .join("185838")
+            .join(attachment_id.as_str().unwrap())
+            .join("notes.txt");
+        tokio::fs::create_dir_all(text_path.parent().unwrap()).await?;
+        tokio::fs::write(&text_path, b"not an image").await?;
+
+        let transport = MemoryTransport::new();
+        transport.respond_json("GET", "project/eggbug/posts", 200, &page);
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":2}"#);
+        let session = session(&transport);
+
+        let summary = import(&session, "eggbug", &dir).await?;
+        assert_eq!(
+            summary.imported,
+            [(PostId(185_838), PostId(2)), (PostId(185_922), PostId(1))]
+        );
+        assert_eq!(summary.skipped_attachments, [(PostId(185_838), text_path)]);
+        let post_requests = transport
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == "POST")
+            .collect::<Vec<_>>();
+        assert_eq!(post_requests.len(), 1);
+        let body: serde_json::Value = post_requests[0].json()?;
+        assert!(body["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|block| block["type"] == "markdown"));
+
+        let log = read_import_log(&dir.join("import-eggbug.log")).await?;
+        assert_eq!(log.done.len(), 2);
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_logs_failure() -> Result<(), Box<dyn std::error::Error>> {
+        let sample: serde_json::Value =
+            serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+        let dir = crate::persist::temp_path("import-logs-failure");
+        tokio::fs::create_dir_all(dir.join("185922")).await?;
+        tokio::fs::write(
+            dir.join("185922").join(POST_FILE_NAME),
+            sample["items"][0].to_string(),
+        )
+        .await?;
+
+        let transport = MemoryTransport::new();
+        transport.respond_json(
+            "GET",
+            "project/eggbug/posts",
+            200,
+            &json!({ "nItems": 0, "nPages": 0, "items": [] }),
+        );
+        transport.respond_once("POST", "project/eggbug/posts", 500, "oops");
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":1}"#);
+        let session = session(&transport);
+
+        assert!(import(&session, "eggbug", &dir).await.is_err());
+        let log = tokio::fs::read_to_string(dir.join("import-eggbug.log")).await?;
+        assert!(log.lines().last().unwrap().starts_with("185922 failed "));
+
+        // Running the import again resumes it.
+        let summary = import(&session, "eggbug", &dir).await?;
+        assert_eq!(summary.imported, [(PostId(185_922), PostId(1))]);
+
+        tokio::fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_attachment_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let post_page: crate::post::PostPage =
+            serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+        let post = post_page
+            .items
+            .iter()
+            .find(|post| post.post_id.0 == 185_838)
+            .expect("Couldn't find post by ID 185838 as expected; did you change the sample?");
+
+        let paths = attachments(post)
+            .into_iter()
+            .map(|(id, url)| attachment_path(Path::new("185838"), id, url))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![Path::new("185838")
+                .join("2b1e7477-ba13-4f7e-9547-f0e2668b92b6")
+                .join("cooltext422710535227689.png")]
+        );
+
+        let id = AttachmentId::default();
+        assert_eq!(
+            attachment_path(Path::new("1"), id, "https://example.invalid/a/b.png?x=y"),
+            Path::new("1").join(id.to_string()).join("b.png")
+        );
+        assert_eq!(
+            attachment_path(Path::new("1"), id, "https://example.invalid/a/"),
+            Path::new("1").join(id.to_string()).join("file")
+        );
+        Ok(())
+    }
+}
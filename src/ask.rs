@@ -1,3 +1,5 @@
+use crate::attachment::{TrpcData, TrpcResponse};
+use crate::{Error, Session};
 use derive_more::{Display, From, FromStr, Into};
 use serde::{Deserialize, Serialize};
 
@@ -23,7 +25,8 @@ pub struct AskId(pub String);
 
 /// Describes the contents of an ask. Asks can't be created client-side, only decoded when reading
 /// content from the server.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Ask {
     pub(crate) ask_id: AskId,
     /// Information about the account that sent this ask, if it wasn't sent anonymously.
@@ -43,10 +46,35 @@ impl Ask {
 }
 
 /// Describes the project that sent an ask.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Asker {
     /// The unique handle of the asker.
     pub handle: String,
     /// The display name of the asker, which may be different from the handle.
     pub display_name: String,
 }
+
+/// Get the asks sent to `project` that haven't been answered yet.
+pub(crate) async fn pending(session: &Session, project: &str) -> Result<Vec<Ask>, Error> {
+    let TrpcResponse {
+        result: TrpcData { data: list },
+    }: TrpcResponse<PendingAsks> = session
+        .client
+        .get("trpc/asks.listPending")
+        .query(&[(
+            "input",
+            serde_json::json!({ "projectHandle": project }).to_string(),
+        )])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(list.asks.into_iter().map(Ask::from).collect())
+}
+
+#[derive(Deserialize)]
+struct PendingAsks {
+    asks: Vec<crate::post::de::Ask>,
+}
@@ -1,12 +1,34 @@
-use crate::{Client, Error, PostId};
+use crate::{Client, Error, PostId, ValidationError};
 use bytes::Bytes;
 use derive_more::{Display, From, FromStr, Into};
+use futures::TryStream;
 use reqwest::multipart::{Form, Part};
 use reqwest::Body;
 use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// The maximum size of an attachment, in bytes. This is the limit for cohost Plus accounts; other
+/// accounts have a smaller limit that only the server checks.
+const MAX_ATTACHMENT_SIZE: u64 = 10 * 1024 * 1024;
+/// Content types cohost accepts for attachments.
+const ATTACHMENT_CONTENT_TYPES: &[&str] = &[
+    "audio/flac",
+    "audio/mp4",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/opus",
+    "audio/wav",
+    "audio/x-wav",
+    "image/avif",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+];
+
 /// An attachment ID.
 #[allow(clippy::module_name_repetitions)]
 #[derive(
@@ -35,7 +57,7 @@ pub struct AttachmentId(pub Uuid);
 /// [`Post`][`crate::Post`] that is created or edited, the client attempts to upload the
 /// attachment. If successful, the attachment becomes ["uploaded"][`Attachment::is_uploaded`]; if
 /// not, the attachment becomes ["failed"][`Attachment::is_failed`].
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Attachment {
     pub(crate) kind: Inner,
 
@@ -44,7 +66,7 @@ pub struct Attachment {
 }
 
 /// Attachment metadata specific to a supported type of media.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 #[serde(untagged)]
 pub enum MediaMetadata {
     /// Image attachments
@@ -66,10 +88,10 @@ pub enum MediaMetadata {
     },
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) enum Inner {
     New {
-        stream: Body,
+        source: Source,
         filename: String,
         content_type: String,
         content_length: u64,
@@ -79,7 +101,68 @@ pub(crate) enum Inner {
     Failed,
 }
 
-#[derive(Debug, Deserialize)]
+/// Where a new attachment's contents are read from. Sources can be read again each time the
+/// attachment is uploaded.
+#[derive(Clone)]
+pub(crate) enum Source {
+    Bytes(Bytes),
+    #[cfg(feature = "fs")]
+    File(std::path::PathBuf),
+    Fn(Arc<dyn Fn() -> Body + Send + Sync>),
+}
+
+impl Source {
+    /// Opens the source for reading. Files are checked to still be `content_length` bytes long.
+    #[cfg_attr(not(feature = "fs"), allow(clippy::unused_async, unused_variables))]
+    async fn open(&self, content_length: u64) -> Result<Body, Error> {
+        Ok(match self {
+            Source::Bytes(bytes) => bytes.clone().into(),
+            #[cfg(feature = "fs")]
+            Source::File(path) => {
+                use tokio_util::codec::{BytesCodec, FramedRead};
+
+                let file = tokio::fs::File::open(path).await?;
+                let actual = file.metadata().await?.len();
+                if actual != content_length {
+                    return Err(Error::AttachmentChanged {
+                        path: path.clone(),
+                        expected: content_length,
+                        actual,
+                    });
+                }
+                Body::wrap_stream(FramedRead::new(file, BytesCodec::new()))
+            }
+            Source::Fn(open) => open(),
+        })
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            #[cfg(feature = "fs")]
+            Source::File(path) => f.debug_tuple("File").field(path).finish(),
+            Source::Fn(_) => f.write_str("Fn"),
+        }
+    }
+}
+
+/// Buffers and files are compared by their contents and paths; functions are only equal to
+/// themselves.
+impl PartialEq for Source {
+    fn eq(&self, other: &Source) -> bool {
+        match (self, other) {
+            (Source::Bytes(a), Source::Bytes(b)) => a == b,
+            #[cfg(feature = "fs")]
+            (Source::File(a), Source::File(b)) => a == b,
+            (Source::Fn(a), Source::Fn(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Finished {
     pub(crate) attachment_id: AttachmentId,
@@ -109,7 +192,7 @@ impl Attachment {
         Attachment {
             kind: Inner::New {
                 content_length: content.len().try_into().unwrap(),
-                stream: content.into(),
+                source: Source::Bytes(content),
                 filename,
                 content_type,
                 metadata: Some(metadata),
@@ -119,15 +202,16 @@ impl Attachment {
     }
 
     /// Create an `Attachment` from a file on disk.
+    ///
+    /// The path is made absolute and the file's length is read now, but its contents are read
+    /// each time the attachment is uploaded. Uploading fails with [`Error::AttachmentChanged`] if
+    /// the file's length has changed by then.
     #[cfg(feature = "fs")]
     pub async fn new_from_file(
         path: impl AsRef<std::path::Path>,
         content_type: String,
         metadata: Option<MediaMetadata>,
     ) -> Result<Attachment, std::io::Error> {
-        use tokio::fs::File;
-        use tokio_util::codec::{BytesCodec, FramedRead};
-
         let path = path.as_ref();
         let filename = path
             .file_name()
@@ -135,9 +219,10 @@ impl Attachment {
             .unwrap_or("file")
             .to_owned();
 
-        let file = File::open(path).await?;
+        let file = tokio::fs::File::open(path).await?;
         let content_length = file.metadata().await?.len();
-        let stream = Body::wrap_stream(FramedRead::new(file, BytesCodec::new()));
+        // Keep reading the same file if the working directory changes.
+        let canonical = tokio::fs::canonicalize(path).await?;
 
         let metadata = if metadata.is_some() {
             metadata
@@ -172,7 +257,7 @@ impl Attachment {
 
         Ok(Attachment {
             kind: Inner::New {
-                stream,
+                source: Source::File(canonical),
                 filename,
                 content_type,
                 content_length,
@@ -182,6 +267,40 @@ impl Attachment {
         })
     }
 
+    /// Create an `Attachment` whose contents are streamed from the stream returned by calling
+    /// `open` each time the attachment is uploaded, such as by downloading them from elsewhere.
+    ///
+    /// `content_length` must be the exact length of the stream `open` returns.
+    pub fn new_from_fn<S>(
+        open: impl Fn() -> S + Send + Sync + 'static,
+        filename: String,
+        content_type: String,
+        content_length: u64,
+        metadata: Option<MediaMetadata>,
+    ) -> Attachment
+    where
+        S: TryStream + Send + Sync + 'static,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        Bytes: From<S::Ok>,
+    {
+        let alt_text = if let Some(MediaMetadata::Image { .. }) = metadata {
+            Some(String::new())
+        } else {
+            None
+        };
+
+        Attachment {
+            kind: Inner::New {
+                source: Source::Fn(Arc::new(move || Body::wrap_stream(open()))),
+                filename,
+                content_type,
+                content_length,
+                metadata,
+            },
+            alt_text,
+        }
+    }
+
     /// Sets new alt text in a builder-style function.
     #[must_use]
     pub fn with_alt_text(self, alt_text: String) -> Attachment {
@@ -214,6 +333,38 @@ impl Attachment {
         }
     }
 
+    /// Checks an attachment that hasn't been uploaded yet against cohost's limits on size and
+    /// type. `index` is the attachment's position in its post, used in the errors returned.
+    pub(crate) fn validate(&self, index: usize) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        if let Inner::New {
+            content_type,
+            content_length,
+            ..
+        } = &self.kind
+        {
+            if *content_length > MAX_ATTACHMENT_SIZE {
+                errors.push(ValidationError::AttachmentTooLarge {
+                    index,
+                    size: *content_length,
+                    max: MAX_ATTACHMENT_SIZE,
+                });
+            }
+            // Ignore parameters like `; charset=...`.
+            let essence = content_type.split(';').next().unwrap_or_default().trim();
+            if !ATTACHMENT_CONTENT_TYPES
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(essence))
+            {
+                errors.push(ValidationError::UnsupportedAttachmentType {
+                    index,
+                    content_type: content_type.clone(),
+                });
+            }
+        }
+        errors
+    }
+
     pub(crate) fn id(&self) -> Option<AttachmentId> {
         match self.kind {
             Inner::Uploaded(Finished { attachment_id, .. }) => Some(attachment_id),
@@ -228,26 +379,55 @@ impl Attachment {
         project: &str,
         id: PostId,
     ) -> Result<(), Error> {
-        let (stream, filename, content_type, content_length, metadata) =
+        self.upload_with(
+            client,
+            "trpc/posts.attachment.start",
+            PostTarget {
+                project_handle: project,
+                post_id: id,
+            },
+            |attachment_id| {
+                format!(
+                    "project/{}/posts/{}/attach/finish/{}",
+                    project, id, attachment_id
+                )
+            },
+        )
+        .await
+    }
+
+    /// Uploads a new attachment using cohost's three-step flow: ask `start_path` for an upload
+    /// URL (sending `target` alongside the file description), upload the file there, then mark
+    /// the upload as finished at the path returned by `finish_path`.
+    pub(crate) async fn upload_with(
+        &mut self,
+        client: &Client,
+        start_path: &str,
+        target: impl Serialize,
+        finish_path: impl FnOnce(AttachmentId) -> String,
+    ) -> Result<(), Error> {
+        let (source, filename, content_type, content_length, metadata) =
             match std::mem::replace(&mut self.kind, Inner::Failed) {
                 Inner::New {
-                    stream,
+                    source,
                     filename,
                     content_type,
                     content_length,
                     metadata,
-                } => (stream, filename, content_type, content_length, metadata),
-                Inner::Uploaded(_) => return Ok(()),
+                } => (source, filename, content_type, content_length, metadata),
+                Inner::Uploaded(finished) => {
+                    self.kind = Inner::Uploaded(finished);
+                    return Ok(());
+                }
                 Inner::Failed => return Err(Error::FailedAttachment),
             };
 
         let TrpcResponse {
             result: TrpcData { data: response },
         }: TrpcResponse<AttachStartResponse> = client
-            .post("trpc/posts.attachment.start")
+            .post(start_path)
             .json(&AttachStartRequest {
-                project_handle: project,
-                post_id: id,
+                target,
                 filename: &filename,
                 content_type: &content_type,
                 content_length,
@@ -260,6 +440,7 @@ impl Attachment {
             .await?;
         tracing::info!(attachment_id = %response.attachment_id);
 
+        let stream = source.open(content_length).await?;
         let mut form = Form::new();
         for (name, value) in response.required_fields {
             form = form.text(name, value);
@@ -272,8 +453,7 @@ impl Attachment {
         );
 
         client
-            .client
-            .post(response.url)
+            .request_url(reqwest::Method::POST, &response.url)
             .multipart(form)
             .send()
             .await?
@@ -281,10 +461,7 @@ impl Attachment {
 
         self.kind = Inner::Uploaded(
             client
-                .post(&format!(
-                    "project/{}/posts/{}/attach/finish/{}",
-                    project, id, response.attachment_id
-                ))
+                .post(&finish_path(response.attachment_id))
                 .send()
                 .await?
                 .error_for_status()?
@@ -295,11 +472,91 @@ impl Attachment {
     }
 }
 
+/// Uploaded attachments are serialized as their ID, URL, and alt text. New attachments can't be
+/// serialized, since their contents are only read while uploading, and neither can failed ones.
+#[cfg(feature = "serde")]
+impl Serialize for Attachment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+
+        match &self.kind {
+            Inner::Uploaded(Finished { attachment_id, url }) => UploadedAttachment {
+                id: *attachment_id,
+                url: url.as_str(),
+                alt_text: self.alt_text.as_deref(),
+            }
+            .serialize(serializer),
+            Inner::New { filename, .. } => Err(S::Error::custom(format!(
+                "attachment {:?} hasn't been uploaded, so it can't be serialized",
+                filename
+            ))),
+            Inner::Failed => Err(S::Error::custom(
+                "attachment failed to upload, so it can't be serialized",
+            )),
+        }
+    }
+}
+
+/// Deserializes an uploaded attachment, as serialized by its [`Serialize`] implementation.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Attachment {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let UploadedAttachment { id, url, alt_text } =
+            UploadedAttachment::<String>::deserialize(deserializer)?;
+        Ok(Attachment {
+            kind: Inner::Uploaded(Finished {
+                attachment_id: id,
+                url,
+            }),
+            alt_text,
+        })
+    }
+}
+
+/// The serialized form of an uploaded [`Attachment`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize, Serialize)]
+struct UploadedAttachment<S> {
+    id: AttachmentId,
+    url: S,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alt_text: Option<S>,
+}
+
+/// Guesses the content type of an attachment from the extension of its file name or path, for
+/// passing to [`Attachment::new_from_file`].
+///
+/// Returns `application/octet-stream` for extensions that aren't images or audio cohost accepts.
+#[must_use]
+pub fn guess_content_type(path: impl AsRef<std::path::Path>) -> String {
+    let extension = path
+        .as_ref()
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/opus",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "m4a" => "audio/mp4",
+        _ => "application/octet-stream",
+    }
+    .to_owned()
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct AttachStartRequest<'a> {
-    project_handle: &'a str,
-    post_id: PostId,
+struct AttachStartRequest<'a, T> {
+    #[serde(flatten)]
+    target: T,
 
     filename: &'a str,
     content_type: &'a str,
@@ -309,6 +566,13 @@ struct AttachStartRequest<'a> {
     metadata: Option<MediaMetadata>,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PostTarget<'a> {
+    project_handle: &'a str,
+    post_id: PostId,
+}
+
 #[derive(Serialize)]
 struct AudioMetadata<'a> {
     artist: &'a str,
@@ -334,11 +598,57 @@ struct AttachStartResponse {
 }
 
 #[derive(Deserialize)]
-struct TrpcResponse<D> {
-    result: TrpcData<D>,
+pub(crate) struct TrpcResponse<D> {
+    pub(crate) result: TrpcData<D>,
 }
 
 #[derive(Deserialize)]
-struct TrpcData<D> {
-    data: D,
+pub(crate) struct TrpcData<D> {
+    pub(crate) data: D,
+}
+
+#[cfg(all(test, feature = "fs"))]
+mod tests {
+    use super::{Inner, Source};
+    use crate::persist::temp_path;
+    use crate::test_util::{mock_attachment_upload, session, PNG};
+    use crate::{Attachment, Error, MemoryTransport, Post};
+
+    #[tokio::test]
+    async fn test_upload_changed_file() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("changed.png");
+        std::fs::write(&path, PNG)?;
+        let attachment = Attachment::new_from_file(&path, "image/png".into(), None).await?;
+        match &attachment.kind {
+            Inner::New {
+                source: Source::File(stored),
+                ..
+            } => assert_eq!(*stored, std::fs::canonicalize(&path)?),
+            kind => panic!("unexpected attachment {:?}", kind),
+        }
+
+        std::fs::write(&path, b"a different egg entirely")?;
+        let transport = MemoryTransport::new();
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":1}"#);
+        mock_attachment_upload(&transport, "eggbug", 1);
+        let mut post = Post {
+            attachments: vec![attachment],
+            ..Post::default()
+        };
+        let err = session(&transport)
+            .create_post("eggbug", &mut post)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::AttachmentChanged {
+                expected: 16,
+                actual: 24,
+                ..
+            }
+        ));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
 }
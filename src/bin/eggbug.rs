@@ -0,0 +1,428 @@
+//! Command-line interface to eggbug-rs, for posting to cohost from scripts.
+//!
+//! Credentials are read from the `COHOST_EMAIL`, `COHOST_PASSWORD`, and `COHOST_PROJECT`
+//! environment variables, falling back to a TOML config file with `email`, `password`, `project`,
+//! and optionally `base_url` keys. Every command prints JSON to stdout.
+
+#![deny(elided_lifetimes_in_paths)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::uninlined_format_args)]
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use eggbug::{guess_content_type, Attachment, Client, Post, PostId, Session};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing_subscriber::{fmt, EnvFilter};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "eggbug",
+    version,
+    about = "Post to cohost from the command line"
+)]
+struct Cli {
+    /// Config file to read credentials from [default: eggbug/config.toml in the XDG config directory]
+    #[arg(long, global = true, env = "EGGBUG_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Project (page) to act on, overriding the environment and config file
+    #[arg(long, short, global = true)]
+    project: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Create a post
+    Post(PostArgs),
+    /// Edit a post, replacing only the parts given
+    Edit {
+        /// ID of the post to edit
+        id: PostId,
+        #[command(flatten)]
+        post: PostArgs,
+        /// Publish a draft
+        #[arg(long, conflicts_with = "draft")]
+        publish: bool,
+    },
+    /// Share a post, optionally with added content
+    Share {
+        /// ID of the post to share
+        id: PostId,
+        #[command(flatten)]
+        post: PostArgs,
+    },
+    /// Delete a post
+    Delete {
+        /// ID of the post to delete
+        id: PostId,
+    },
+    /// List a page of posts
+    List {
+        /// Page number, starting from 0
+        #[arg(long, default_value_t = 0)]
+        page: u64,
+    },
+    /// Get a single post
+    Get {
+        /// ID of the post to get
+        id: PostId,
+    },
+}
+
+#[derive(Debug, Args)]
+struct PostArgs {
+    /// Post headline
+    #[arg(long)]
+    headline: Option<String>,
+    /// File to read the markdown body from, or `-` for stdin
+    #[arg(long, value_name = "FILE")]
+    body: Option<PathBuf>,
+    /// Tag to add (repeatable; when editing, replaces all tags)
+    #[arg(long = "tag", short, value_name = "TAG")]
+    tags: Vec<String>,
+    /// Content warning to add (repeatable; when editing, replaces all content warnings)
+    #[arg(long = "cw", value_name = "CW")]
+    content_warnings: Vec<String>,
+    /// File to attach (repeatable; when editing, replaces all attachments)
+    #[arg(long = "attach", short, value_name = "FILE")]
+    attachments: Vec<PathBuf>,
+    /// Alt text for each attachment, in the same order as --attach (repeatable)
+    #[arg(long = "alt", value_name = "TEXT")]
+    alt_text: Vec<String>,
+    /// Mark the post as 18+
+    #[arg(long)]
+    adult: bool,
+    /// Save the post as a draft
+    #[arg(long)]
+    draft: bool,
+}
+
+/// Credentials and settings from the config file. Any of them can be left out.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    email: Option<String>,
+    password: Option<String>,
+    project: Option<String>,
+    base_url: Option<String>,
+}
+
+impl Config {
+    /// Loads the config file, if any, and applies environment variables and `--project` over it.
+    fn load(cli: &Cli) -> Result<Config> {
+        let mut config = match &cli.config {
+            Some(path) => Config::read(path)?,
+            None => match default_config_path() {
+                Some(path) if path.exists() => Config::read(&path)?,
+                _ => Config::default(),
+            },
+        };
+        for (var, field) in [
+            ("COHOST_EMAIL", &mut config.email),
+            ("COHOST_PASSWORD", &mut config.password),
+            ("COHOST_PROJECT", &mut config.project),
+        ] {
+            if let Ok(value) = std::env::var(var) {
+                *field = Some(value);
+            }
+        }
+        if let Some(project) = &cli.project {
+            config.project = Some(project.clone());
+        }
+        Ok(config)
+    }
+
+    fn read(path: &Path) -> Result<Config> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    fn project(&self) -> Result<&str> {
+        self.project
+            .as_deref()
+            .ok_or_else(|| anyhow!("no project set; use --project or COHOST_PROJECT"))
+    }
+
+    fn client(&self) -> Client {
+        match &self.base_url {
+            Some(base_url) => Client::new().with_base_url(base_url.clone()),
+            None => Client::new(),
+        }
+    }
+
+    /// Returns a logged-in client if credentials are set, so that drafts and private posts can be
+    /// read, or a logged-out client otherwise.
+    async fn reader(&self) -> Result<Client> {
+        if self.email.is_some() && self.password.is_some() {
+            Ok(self.login().await?.as_client().clone())
+        } else {
+            Ok(self.client())
+        }
+    }
+
+    async fn login(&self) -> Result<Session> {
+        let (Some(email), Some(password)) = (&self.email, &self.password) else {
+            bail!("no credentials set; use COHOST_EMAIL and COHOST_PASSWORD or a config file");
+        };
+        Ok(self.client().login(email, password).await?)
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("eggbug").join("config.toml"))
+}
+
+impl PostArgs {
+    /// Applies these arguments to `post`, leaving out anything that wasn't given.
+    async fn apply(self, post: &mut Post) -> Result<()> {
+        if self.alt_text.len() > self.attachments.len() {
+            bail!("more --alt values than --attach files");
+        }
+
+        if let Some(headline) = self.headline {
+            post.headline = headline;
+        }
+        if let Some(path) = self.body {
+            post.markdown = read_body(&path)?;
+        }
+        if !self.tags.is_empty() {
+            post.tags = self.tags;
+        }
+        if !self.content_warnings.is_empty() {
+            post.content_warnings = self.content_warnings;
+        }
+        if !self.attachments.is_empty() {
+            post.attachments.clear();
+        }
+        let mut alt_text = self.alt_text.into_iter();
+        for path in self.attachments {
+            let content_type = guess_content_type(&path);
+            let mut attachment = Attachment::new_from_file(&path, content_type, None)
+                .await
+                .with_context(|| format!("failed to open {}", path.display()))?;
+            if let Some(alt_text) = alt_text.next() {
+                attachment = attachment.with_alt_text(alt_text);
+            }
+            post.attachments.push(attachment);
+        }
+        post.adult_content |= self.adult;
+        post.draft |= self.draft;
+        Ok(())
+    }
+}
+
+fn read_body(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut body = String::new();
+        std::io::stdin()
+            .read_to_string(&mut body)
+            .context("failed to read stdin")?;
+        Ok(body)
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+    }
+}
+
+/// Looks through a project's pages for a post.
+async fn find_post(client: &Client, project: &str, id: PostId) -> Result<Post> {
+    for page in 0.. {
+        let posts = client.get_posts_page(project, page).await?;
+        if posts.is_empty() {
+            break;
+        }
+        if let Some(post) = posts
+            .into_iter()
+            .find(|post| post.metadata.as_ref().map(|m| m.locations.id) == Some(id))
+        {
+            return Ok(post);
+        }
+    }
+    bail!("post {} not found on {}", id, project)
+}
+
+fn post_to_json(post: &Post) -> Value {
+    let metadata = post.metadata.as_ref();
+    json!({
+        "id": metadata.map(|m| m.locations.id),
+        "url": metadata.map(|m| &m.locations.url),
+        "project": metadata.map(|m| &m.posting_project_id),
+        "published_at": metadata.map(|m| m.publication_date),
+        "headline": post.headline,
+        "markdown": post.markdown,
+        "tags": post.tags,
+        "content_warnings": post.content_warnings,
+        "adult_content": post.adult_content,
+        "draft": post.draft,
+        "attachments": post.attachments.iter().map(|attachment| json!({
+            "url": attachment.url(),
+            "alt_text": attachment.alt_text,
+        })).collect::<Vec<_>>(),
+        "ask": post.ask.as_ref().map(|ask| json!({
+            "id": ask.id(),
+            "asker": ask.asker.as_ref().map(|asker| &asker.handle),
+            "content": ask.content,
+            "sent_at": ask.sent_at,
+        })),
+    })
+}
+
+async fn run(cli: Cli) -> Result<Value> {
+    let config = Config::load(&cli)?;
+    let project = config.project()?;
+    Ok(match cli.command {
+        Command::Post(args) => {
+            let mut post = Post::default();
+            args.apply(&mut post).await?;
+            let session = config.login().await?;
+            json!({ "id": session.create_post(project, &mut post).await? })
+        }
+        Command::Edit { id, post, publish } => {
+            let session = config.login().await?;
+            let mut existing = find_post(session.as_client(), project, id).await?;
+            post.apply(&mut existing).await?;
+            if publish {
+                existing.draft = false;
+            }
+            json!({ "id": session.edit_post(project, id, &mut existing).await? })
+        }
+        Command::Share { id, post: args } => {
+            let mut post = Post::default();
+            args.apply(&mut post).await?;
+            let session = config.login().await?;
+            json!({ "id": session.share_post(project, id, &mut post).await? })
+        }
+        Command::Delete { id } => {
+            config.login().await?.delete_post(project, id).await?;
+            json!({ "id": id, "deleted": true })
+        }
+        Command::List { page } => {
+            let posts = config.reader().await?.get_posts_page(project, page).await?;
+            posts.iter().map(post_to_json).collect()
+        }
+        Command::Get { id } => {
+            post_to_json(&find_post(&config.reader().await?, project, id).await?)
+        }
+    })
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let output = run(Cli::parse()).await?;
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cli, Command, Config};
+    use clap::{CommandFactory, Parser};
+    use eggbug::{Attachment, MediaMetadata, Post};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_post() -> Result<(), Box<dyn std::error::Error>> {
+        let cli = Cli::try_parse_from([
+            "eggbug",
+            "post",
+            "--headline",
+            "hello",
+            "-t",
+            "one",
+            "-t",
+            "two",
+            "--attach",
+            "a.png",
+            "--alt",
+            "an image",
+            "--draft",
+        ])?;
+        let Command::Post(args) = cli.command else {
+            panic!("expected post command");
+        };
+        assert_eq!(args.headline.as_deref(), Some("hello"));
+        assert_eq!(args.tags, ["one", "two"]);
+        assert_eq!(args.alt_text, ["an image"]);
+        assert!(args.draft);
+
+        assert!(Cli::try_parse_from(["eggbug", "edit", "1", "--draft", "--publish"]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_edit_replaces_attachments() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("eggbug-{}-new.png", std::process::id()));
+        std::fs::write(&path, "not really a png")?;
+        let mut post = Post {
+            attachments: vec![Attachment::new(
+                "also not a png",
+                "old.png".into(),
+                "image/png".into(),
+                MediaMetadata::Image {
+                    width: None,
+                    height: None,
+                },
+            )],
+            ..Post::default()
+        };
+
+        let cli = Cli::try_parse_from(["eggbug", "edit", "1", "--headline", "hello"])?;
+        let Command::Edit { post: args, .. } = cli.command else {
+            panic!("expected edit command");
+        };
+        args.apply(&mut post).await?;
+        assert_eq!(post.attachments.len(), 1);
+
+        // Running the same edit twice leaves one copy of the attachment.
+        for _ in 0..2 {
+            let cli = Cli::try_parse_from([
+                "eggbug".as_ref(),
+                "edit".as_ref(),
+                "1".as_ref(),
+                "--attach".as_ref(),
+                path.as_os_str(),
+            ])?;
+            let Command::Edit { post: args, .. } = cli.command else {
+                panic!("expected edit command");
+            };
+            args.apply(&mut post).await?;
+            assert_eq!(post.attachments.len(), 1);
+        }
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_config() -> Result<(), Box<dyn std::error::Error>> {
+        let config: Config = toml::from_str(
+            r#"
+                email = "eggbug@website.invalid"
+                password = "hunter2"
+                project = "eggbug"
+            "#,
+        )?;
+        assert_eq!(config.project()?, "eggbug");
+        assert!(config.base_url.is_none());
+        assert!(toml::from_str::<Config>("username = \"eggbug\"").is_err());
+        Ok(())
+    }
+}
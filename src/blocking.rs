@@ -0,0 +1,228 @@
+//! A blocking API, for code that isn't async.
+//!
+//! [`Client`] and [`Session`] mirror [`crate::Client`] and [`crate::Session`], running each request
+//! to completion on a Tokio runtime they share. Like [`reqwest::blocking`], they must not be
+//! used from within an async runtime; calling their methods from async code panics.
+//!
+//! [`reqwest::blocking`]: https://docs.rs/reqwest/0.11/reqwest/blocking/index.html
+//!
+//! ```no_run
+//! use eggbug::{blocking::Session, Post};
+//!
+//! # fn f() -> Result<(), Box<dyn std::error::Error>> {
+//! let session = Session::login("eggbug@website.invalid", "hunter2")?;
+//! let mut post = Post {
+//!     headline: "hello from eggbug-rs!".into(),
+//!     ..Default::default()
+//! };
+//! let id = session.create_post("eggbug", &mut post)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Error, Post, PostId, SearchResults};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// Blocking HTTP client. See [`crate::Client`].
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: crate::Client,
+    runtime: Arc<Runtime>,
+}
+
+/// Blocking logged-in session. See [`crate::Session`].
+#[derive(Debug, Clone)]
+pub struct Session {
+    client: Client,
+    inner: crate::Session,
+}
+
+impl Client {
+    /// Creates a new `Client` with the default base URL. See [`crate::Client::new`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the Tokio runtime can't be created.
+    #[must_use]
+    pub fn new() -> Client {
+        Client::from(crate::Client::new())
+    }
+
+    /// Creates a new `Client` with a custom base URL. See [`crate::Client::with_base_url`].
+    #[must_use]
+    pub fn with_base_url(self, base_url: String) -> Client {
+        Client {
+            inner: self.inner.with_base_url(base_url),
+            ..self
+        }
+    }
+
+    /// Sets the transport used to send requests, in a builder-style function. See
+    /// [`crate::Client::with_transport`].
+    #[must_use]
+    pub fn with_transport(self, transport: impl crate::Transport + 'static) -> Client {
+        Client {
+            inner: self.inner.with_transport(transport),
+            ..self
+        }
+    }
+
+    /// Logs into cohost with an email and password, returning a [`Session`]. See
+    /// [`crate::Client::login`].
+    pub fn login(self, email: &str, password: &str) -> Result<Session, Error> {
+        let inner = self
+            .runtime
+            .block_on(self.inner.clone().login(email, password))?;
+        Ok(Session {
+            client: Client {
+                inner: inner.as_client().clone(),
+                runtime: self.runtime,
+            },
+            inner,
+        })
+    }
+
+    /// Returns true if this client has logged in before. See [`crate::Client::has_logged_in`].
+    #[must_use]
+    pub fn has_logged_in(&self) -> bool {
+        self.inner.has_logged_in()
+    }
+
+    /// Get a page of posts from the given project. See [`crate::Client::get_posts_page`].
+    pub fn get_posts_page(&self, project: &str, page: u64) -> Result<Vec<Post>, Error> {
+        self.runtime
+            .block_on(self.inner.get_posts_page(project, page))
+    }
+
+    /// Search for projects, tags, and posts matching `query`. See [`crate::Client::search`].
+    pub fn search(&self, query: &str, page: u64) -> Result<SearchResults, Error> {
+        self.runtime.block_on(self.inner.search(query, page))
+    }
+
+    /// Create an [`Attachment`][`crate::Attachment`] from a file on disk. See
+    /// [`crate::Attachment::new_from_file`].
+    ///
+    /// The file is read while the attachment is uploaded, which must happen through this client
+    /// or a [`Session`] created from it.
+    #[cfg(feature = "fs")]
+    pub fn attachment_from_file(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        content_type: String,
+        metadata: Option<crate::MediaMetadata>,
+    ) -> Result<crate::Attachment, std::io::Error> {
+        self.runtime.block_on(crate::Attachment::new_from_file(
+            path,
+            content_type,
+            metadata,
+        ))
+    }
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
+}
+
+impl From<crate::Client> for Client {
+    /// Wraps an async client, creating a new Tokio runtime for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the Tokio runtime can't be created.
+    fn from(inner: crate::Client) -> Client {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to create Tokio runtime");
+        Client {
+            inner,
+            runtime: Arc::new(runtime),
+        }
+    }
+}
+
+impl Session {
+    /// Returns the inner [`Client`] for this session. See [`crate::Session::as_client`].
+    #[must_use]
+    pub fn as_client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Logs into cohost with an email and password, returning a `Session`. See
+    /// [`crate::Session::login`].
+    pub fn login(email: &str, password: &str) -> Result<Session, Error> {
+        Client::new().login(email, password)
+    }
+
+    /// Create a post. See [`crate::Session::create_post`].
+    pub fn create_post(&self, page: &str, post: &mut Post) -> Result<PostId, Error> {
+        self.client
+            .runtime
+            .block_on(self.inner.create_post(page, post))
+    }
+
+    /// Share a post. See [`crate::Session::share_post`].
+    pub fn share_post(
+        &self,
+        page: &str,
+        shared_post: PostId,
+        post: &mut Post,
+    ) -> Result<PostId, Error> {
+        self.client
+            .runtime
+            .block_on(self.inner.share_post(page, shared_post, post))
+    }
+
+    /// Edit a post. See [`crate::Session::edit_post`].
+    pub fn edit_post(&self, page: &str, id: PostId, post: &mut Post) -> Result<PostId, Error> {
+        self.client
+            .runtime
+            .block_on(self.inner.edit_post(page, id, post))
+    }
+
+    /// Delete a post. See [`crate::Session::delete_post`].
+    pub fn delete_post(&self, page: &str, id: PostId) -> Result<(), Error> {
+        self.client
+            .runtime
+            .block_on(self.inner.delete_post(page, id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Client;
+    use crate::{MemoryTransport, Post, PostId};
+    use serde_json::json;
+
+    #[test]
+    fn test_blocking() -> Result<(), Box<dyn std::error::Error>> {
+        let transport = MemoryTransport::new();
+        transport.respond_json(
+            "GET",
+            "project/eggbug/posts",
+            200,
+            &json!({ "nItems": 0, "nPages": 0, "items": [] }),
+        );
+        transport.respond_json("POST", "project/eggbug/posts", 200, &json!({ "postId": 1 }));
+        transport.respond("DELETE", "project/eggbug/posts/1", 204, "");
+
+        let client = Client::new().with_transport(transport.clone());
+        assert!(client.get_posts_page("eggbug", 0)?.is_empty());
+
+        let session = super::Session {
+            inner: crate::test_util::session(&transport),
+            client,
+        };
+        let mut post = Post {
+            headline: "hello".into(),
+            ..Post::default()
+        };
+        assert_eq!(session.create_post("eggbug", &mut post)?, PostId(1));
+        session.delete_post("eggbug", PostId(1))?;
+        assert_eq!(transport.requests().len(), 3);
+        Ok(())
+    }
+}
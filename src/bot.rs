@@ -0,0 +1,634 @@
+//! A runtime for bots that react to asks, comments, tags, and the clock.
+//!
+//! A [`Bot`] is built by registering handlers, then [run][`Bot::run`] with an account's
+//! credentials. Each tick, it checks the account's notifications and the tags it's watching, and
+//! calls the handlers for anything new, oldest first; then it calls any scheduled handlers that
+//! are due. Where it has got to is kept in a JSON state file, so nothing is handled twice across
+//! restarts.
+//!
+//! Events are handled at most once. If a handler returns an error, the error is logged and the
+//! bot moves on; the event isn't passed to the handler again. Handlers that need to retry must
+//! do so themselves.
+//!
+//! ```no_run
+//! use eggbug::{bot::Bot, Client, Post};
+//! use std::time::Duration;
+//!
+//! # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+//! let bot = Bot::open("eggbug", "bot-state.json")
+//!     .await?
+//!     .on_ask(|cx, ask| async move {
+//!         println!("new ask on {}: {}", cx.project, ask.ask.content);
+//!         Ok(())
+//!     })
+//!     .on_schedule("hourly", Duration::from_secs(60 * 60), |cx| async move {
+//!         let mut post = Post {
+//!             headline: "it's a new hour".into(),
+//!             ..Default::default()
+//!         };
+//!         cx.session.create_post(&cx.project, &mut post).await?;
+//!         Ok(())
+//!     });
+//! let shutdown = async {
+//!     // Stop after the current tick when asked to, e.g. on Ctrl-C.
+//! };
+//! bot.run(Client::new(), "eggbug@website.invalid", "hunter2", shutdown)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+// `Duration::from_mins` and `Duration::from_hours` need Rust 1.91.
+#![allow(unknown_lints, clippy::duration_suboptimal_units)]
+
+use crate::persist::save_json;
+use crate::{
+    Ask, Client, CommentId, Error, Notification, NotificationCursor, Post, PostId, Project,
+    Session, TagCursor, TagFeedOptions,
+};
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, Either};
+use futures::{Future, FutureExt, StreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often the bot checks for new notifications and posts, unless changed with
+/// [`Bot::with_interval`].
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+type Handler<T> = Box<dyn Fn(Context, T) -> BoxFuture<'static, Result<(), Error>> + Send + Sync>;
+
+/// What a handler is given to act with: the bot's session and the project it runs as.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Context {
+    /// The bot's logged-in session.
+    pub session: Session,
+    /// The handle of the project the bot runs as.
+    pub project: String,
+}
+
+/// A new ask sent to the bot's project, passed to [`Bot::on_ask`] handlers.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct NewAsk {
+    /// The ask, which can be answered with [`Session::answer_ask`].
+    pub ask: Ask,
+    /// The project that sent the ask, or `None` if it was sent anonymously.
+    pub from_project: Option<Project>,
+}
+
+/// A new comment on one of the account's posts, passed to [`Bot::on_comment`] handlers.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct NewComment {
+    /// The ID of the comment.
+    pub comment_id: CommentId,
+    /// The comment this comment is replying to, if any.
+    pub in_reply_to: Option<CommentId>,
+    /// The post that was commented on.
+    pub to_post: PostId,
+    /// The project that made the comment.
+    pub from_project: Project,
+    /// The time the comment was made.
+    pub created_at: DateTime<Utc>,
+    /// The comment's markdown content.
+    pub body: String,
+}
+
+/// An event-driven bot, running handlers for new asks, comments, and tagged posts, and on a
+/// schedule.
+///
+/// Handlers run one at a time, in the order events happened. If a handler fails, the error is
+/// logged and the bot moves on; the event is not retried.
+pub struct Bot {
+    project: String,
+    path: PathBuf,
+    interval: Duration,
+    asks: Vec<Handler<NewAsk>>,
+    comments: Vec<Handler<NewComment>>,
+    tags: Vec<(String, Handler<Post>)>,
+    schedules: Vec<(String, Duration, Handler<()>)>,
+    state: State,
+}
+
+/// Where the bot has got to, persisted to its state file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct State {
+    /// Where the bot has got to in the account's notifications.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notifications: Option<NotificationCursor>,
+    /// The publication time of the newest post handled, by tag.
+    #[serde(default)]
+    tags: BTreeMap<String, DateTime<Utc>>,
+    /// The time each scheduled handler last ran, by name.
+    #[serde(default)]
+    schedules: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl fmt::Debug for Bot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bot")
+            .field("project", &self.project)
+            .field("path", &self.path)
+            .field("interval", &self.interval)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Bot {
+    /// Opens a bot running as `project`, with its state file at `path`. If the state file doesn't
+    /// exist, the bot starts from scratch.
+    pub async fn open(project: impl Into<String>, path: impl AsRef<Path>) -> Result<Bot, Error> {
+        let path = path.as_ref().to_owned();
+        let state = match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Bot {
+            project: project.into(),
+            path,
+            interval: DEFAULT_INTERVAL,
+            asks: Vec::new(),
+            comments: Vec::new(),
+            tags: Vec::new(),
+            schedules: Vec::new(),
+            state,
+        })
+    }
+
+    /// Sets how often the bot checks for new notifications and posts, in a builder-style
+    /// function. The default is one minute.
+    #[must_use]
+    pub fn with_interval(self, interval: Duration) -> Bot {
+        Bot { interval, ..self }
+    }
+
+    /// Registers a handler for new asks, in a builder-style function.
+    ///
+    /// Only asks sent to the bot's project are handled. Asks sent before the bot first ran are
+    /// skipped, as are asks that have already been answered or deleted by the time the bot sees
+    /// them.
+    #[must_use]
+    pub fn on_ask<F, Fut>(mut self, handler: F) -> Bot
+    where
+        F: Fn(Context, NewAsk) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.asks
+            .push(Box::new(move |cx, ask| handler(cx, ask).boxed()));
+        self
+    }
+
+    /// Registers a handler for new comments on the bot's project's posts, in a builder-style
+    /// function.
+    ///
+    /// Comments made before the bot first ran are skipped, as are comments that have been deleted
+    /// by the time the bot sees them and comments on posts cohost doesn't say are the project's.
+    #[must_use]
+    pub fn on_comment<F, Fut>(mut self, handler: F) -> Bot
+    where
+        F: Fn(Context, NewComment) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.comments
+            .push(Box::new(move |cx, comment| handler(cx, comment).boxed()));
+        self
+    }
+
+    /// Registers a handler for new posts in `tag`, in a builder-style function.
+    ///
+    /// Posts published before the bot first watched the tag are skipped.
+    #[must_use]
+    pub fn on_tag<F, Fut>(mut self, tag: impl Into<String>, handler: F) -> Bot
+    where
+        F: Fn(Context, Post) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.tags.push((
+            tag.into(),
+            Box::new(move |cx, post| handler(cx, post).boxed()),
+        ));
+        self
+    }
+
+    /// Registers a handler to run every `every`, in a builder-style function.
+    ///
+    /// `name` identifies the handler in the state file. A handler that has never run is run on the
+    /// bot's first tick. Schedules are checked at least once per [interval][`Bot::with_interval`],
+    /// so shorter schedules still run every interval.
+    #[must_use]
+    pub fn on_schedule<F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        every: Duration,
+        handler: F,
+    ) -> Bot
+    where
+        F: Fn(Context) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        self.schedules.push((
+            name.into(),
+            every,
+            Box::new(move |cx, ()| handler(cx).boxed()),
+        ));
+        self
+    }
+
+    /// Logs in and runs the bot until `shutdown` completes.
+    ///
+    /// The bot checks for new events every interval, and sooner if a scheduled handler is due.
+    /// When `shutdown` completes, the bot stops before the next tick, so handlers are never
+    /// interrupted partway through. If cohost stops accepting the session, the bot logs in again.
+    ///
+    /// Other errors are logged and retried on the next tick. Errors reading or writing the state
+    /// file, and failing to log in the first time, are returned.
+    pub async fn run(
+        mut self,
+        client: Client,
+        email: &str,
+        password: &str,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), Error> {
+        let mut session = client.clone().login(email, password).await?;
+        let mut shutdown = Box::pin(shutdown);
+        loop {
+            match self.run_once(&session).await {
+                Ok(()) => {}
+                Err(Error::Io(err)) => return Err(err.into()),
+                Err(err) if is_unauthorized(&err) => {
+                    tracing::info!("session expired; logging in again");
+                    match client.clone().login(email, password).await {
+                        Ok(new) => session = new,
+                        Err(err) => tracing::warn!(%err, "failed to log in"),
+                    }
+                }
+                Err(err) => tracing::warn!(%err, "failed to check for new events"),
+            }
+
+            let sleep = Box::pin(tokio::time::sleep(self.next_wait()));
+            if let Either::Left(_) = futures::future::select(&mut shutdown, sleep).await {
+                tracing::info!("shutting down");
+                return Ok(());
+            }
+        }
+    }
+
+    /// Checks for new events once, calling handlers for each of them, and runs any scheduled
+    /// handlers that are due.
+    ///
+    /// Progress is saved to the state file as it's made, so if an error is returned partway
+    /// through, events that were already handled won't be handled again.
+    pub async fn run_once(&mut self, session: &Session) -> Result<(), Error> {
+        let cx = Context {
+            session: session.clone(),
+            project: self.project.clone(),
+        };
+        if !self.asks.is_empty() || !self.comments.is_empty() {
+            self.check_notifications(&cx).await?;
+        }
+        for index in 0..self.tags.len() {
+            self.check_tag(&cx, index).await?;
+        }
+        self.run_schedules(&cx).await
+    }
+
+    async fn check_notifications(&mut self, cx: &Context) -> Result<(), Error> {
+        let Some(mut cursor) = self.state.notifications.clone() else {
+            // Nothing has been seen yet, so start from the newest notifications.
+            let newest = cx.session.notifications().await?;
+            self.state.notifications = Some(crate::notification::newest(&newest));
+            return self.save().await;
+        };
+
+        // Notifications only carry an ask's ID, so its content is looked up in the inbox, once
+        // per check.
+        let mut pending: Option<Vec<Ask>> = None;
+        for notification in cx.session.notifications_since(&cursor).await? {
+            cursor.advance(&notification);
+            match notification {
+                Notification::Ask {
+                    from_project,
+                    ask_id,
+                    ..
+                } if !self.asks.is_empty() => {
+                    if pending.is_none() {
+                        pending = Some(cx.session.pending_asks(&cx.project).await?);
+                    }
+                    let ask = pending.iter().flatten().find(|ask| ask.ask_id == ask_id);
+                    // The inbox only holds the bot's project's asks, so this also skips asks sent
+                    // to the account's other projects.
+                    if let Some(ask) = ask {
+                        let ask = NewAsk {
+                            ask: ask.clone(),
+                            from_project,
+                        };
+                        for handler in &self.asks {
+                            log_failure("ask", handler(cx.clone(), ask.clone()).await);
+                        }
+                    } else {
+                        tracing::debug!(%ask_id, "skipping ask that isn't pending for the project");
+                    }
+                }
+                Notification::Comment {
+                    created_at,
+                    from_project,
+                    to_post,
+                    to_project,
+                    comment_id,
+                    in_reply_to,
+                    body,
+                } => {
+                    if to_project.as_deref() != Some(cx.project.as_str()) {
+                        tracing::debug!(%comment_id, "skipping comment on another project's post");
+                    } else if let Some(body) = body {
+                        let comment = NewComment {
+                            comment_id,
+                            in_reply_to,
+                            to_post,
+                            from_project,
+                            created_at,
+                            body,
+                        };
+                        for handler in &self.comments {
+                            log_failure("comment", handler(cx.clone(), comment.clone()).await);
+                        }
+                    } else {
+                        tracing::debug!(%comment_id, "skipping deleted comment");
+                    }
+                }
+                _ => {}
+            }
+            self.state.notifications = Some(cursor.clone());
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    async fn check_tag(&mut self, cx: &Context, index: usize) -> Result<(), Error> {
+        let tag = self.tags[index].0.clone();
+        let newest = self.state.tags.get(&tag).copied();
+
+        // Tag feeds are newest first; read back until reaching posts that have been handled.
+        let mut posts = Vec::new();
+        let mut stream = Box::pin(cx.session.client.tagged_posts(
+            &tag,
+            TagFeedOptions::default(),
+            TagCursor::now(),
+        ));
+        while let Some(post) = stream.next().await {
+            let post = post?;
+            let Some(published) = post.metadata.as_ref().map(|m| m.publication_date) else {
+                continue;
+            };
+            if newest.is_some_and(|newest| published <= newest) {
+                break;
+            }
+            posts.push((published, post));
+            if newest.is_none() {
+                break;
+            }
+        }
+        drop(stream);
+
+        if newest.is_none() {
+            // The tag hasn't been watched before, so start from its newest post.
+            let start = posts
+                .first()
+                .map_or_else(Utc::now, |(published, _)| *published);
+            self.state.tags.insert(tag, start);
+            return self.save().await;
+        }
+
+        for (published, post) in posts.into_iter().rev() {
+            log_failure("tag", (self.tags[index].1)(cx.clone(), post).await);
+            self.state.tags.insert(tag.clone(), published);
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    async fn run_schedules(&mut self, cx: &Context) -> Result<(), Error> {
+        for (name, every, handler) in &self.schedules {
+            let now = Utc::now();
+            let due = match self.state.schedules.get(name) {
+                Some(last) => next_run(*last, *every).is_some_and(|next| now >= next),
+                None => true,
+            };
+            if due {
+                log_failure("schedule", handler(cx.clone(), ()).await);
+                self.state.schedules.insert(name.clone(), now);
+                self.save().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns how long to wait before the next tick: the interval, or less if a scheduled
+    /// handler is due sooner.
+    fn next_wait(&self) -> Duration {
+        let now = Utc::now();
+        self.schedules
+            .iter()
+            .filter_map(|(name, every, _)| {
+                let next = next_run(*self.state.schedules.get(name)?, *every)?;
+                Some((next - now).to_std().unwrap_or_default())
+            })
+            .fold(self.interval, Duration::min)
+    }
+
+    async fn save(&self) -> Result<(), Error> {
+        save_json(&self.path, &self.state).await
+    }
+}
+
+fn log_failure(kind: &str, result: Result<(), Error>) {
+    if let Err(err) = result {
+        tracing::warn!(%err, kind, "handler failed");
+    }
+}
+
+/// Returns true if cohost rejected a request because the session is no longer valid.
+fn is_unauthorized(err: &Error) -> bool {
+    let Error::Request(err) = err else {
+        return false;
+    };
+    matches!(
+        err.status(),
+        Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+    )
+}
+
+/// Returns when a scheduled handler that last ran at `last` is next due, or `None` if that's too
+/// far in the future to represent.
+fn next_run(last: DateTime<Utc>, every: Duration) -> Option<DateTime<Utc>> {
+    last.checked_add_signed(chrono::Duration::from_std(every).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bot, DEFAULT_INTERVAL};
+    use crate::persist::temp_path;
+    use crate::test_util::session;
+    use crate::MemoryTransport;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_run_once() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("bot.json");
+        std::fs::write(
+            &path,
+            json!({
+                "notifications": { "createdAt": "2022-11-04T19:00:00Z" },
+                "tags": { "example": "2022-11-04T03:29:00Z" },
+            })
+            .to_string(),
+        )?;
+
+        let transport = MemoryTransport::new();
+        transport.respond_once(
+            "GET",
+            "notifications/list",
+            200,
+            include_str!("../samples/notifications.list.json"),
+        );
+        transport.respond(
+            "GET",
+            "trpc/asks.listPending",
+            200,
+            include_str!("../samples/asks.list-pending.json"),
+        );
+        transport.respond(
+            "GET",
+            "tagged/example",
+            200,
+            include_str!("../samples/example.project.posts.json"),
+        );
+        let session = session(&transport);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (asks, comments, tags, schedules) = (
+            events.clone(),
+            events.clone(),
+            events.clone(),
+            events.clone(),
+        );
+        let mut bot = Bot::open("example", &path)
+            .await?
+            .on_ask(move |_, ask| {
+                let asks = asks.clone();
+                async move {
+                    let anonymous = ask.from_project.is_none();
+                    asks.lock()
+                        .unwrap()
+                        .push(format!("ask {} {}", anonymous, ask.ask.content));
+                    Ok(())
+                }
+            })
+            .on_comment(move |_, comment| {
+                let comments = comments.clone();
+                async move {
+                    let handle = comment.from_project.handle;
+                    comments
+                        .lock()
+                        .unwrap()
+                        .push(format!("comment {} {}", handle, comment.body));
+                    Ok(())
+                }
+            })
+            .on_tag("example", move |_, post| {
+                let tags = tags.clone();
+                async move {
+                    tags.lock().unwrap().push(format!("tag {}", post.headline));
+                    Ok(())
+                }
+            })
+            .on_schedule("daily", Duration::from_secs(24 * 60 * 60), move |cx| {
+                let schedules = schedules.clone();
+                async move {
+                    schedules
+                        .lock()
+                        .unwrap()
+                        .push(format!("schedule {}", cx.project));
+                    Ok(())
+                }
+            });
+        bot.run_once(&session).await?;
+
+        // Notifications come oldest first.
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "comment example-adult !roll 2d6",
+                "ask true !roll 1d20",
+                "tag Commentary repost of a post from an adult account from a non adult account",
+                "schedule example"
+            ]
+        );
+        let state: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)?;
+        assert_eq!(
+            state["notifications"]["createdAt"],
+            "2022-11-05T18:12:40.201Z"
+        );
+        assert_eq!(state["tags"]["example"], "2022-11-04T03:29:25.010Z");
+        assert!(state["schedules"]["daily"].is_string());
+
+        // The schedule isn't due again for a day, so the bot waits the full interval.
+        assert_eq!(bot.next_wait(), DEFAULT_INTERVAL);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_skips_other_projects() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("bot-other-projects.json");
+        std::fs::write(
+            &path,
+            json!({ "notifications": { "createdAt": "2022-11-04T19:00:00Z" } }).to_string(),
+        )?;
+        let transport = MemoryTransport::new();
+        transport.respond_once(
+            "GET",
+            "notifications/list",
+            200,
+            include_str!("../samples/notifications.list.json"),
+        );
+        transport.respond_json(
+            "GET",
+            "trpc/asks.listPending",
+            200,
+            &json!({ "result": { "data": { "asks": [] } } }),
+        );
+        let session = session(&transport);
+
+        // The comment is on a post by `example`, and the ask isn't in this project's inbox.
+        let handled = Arc::new(Mutex::new(0));
+        let (asks, comments) = (handled.clone(), handled.clone());
+        let mut bot = Bot::open("example-adult", &path)
+            .await?
+            .on_ask(move |_, _| {
+                *asks.lock().unwrap() += 1;
+                async { Ok(()) }
+            })
+            .on_comment(move |_, _| {
+                *comments.lock().unwrap() += 1;
+                async { Ok(()) }
+            });
+        bot.run_once(&session).await?;
+        assert_eq!(*handled.lock().unwrap(), 0);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,836 @@
+//! Mirroring RSS and Atom feeds onto cohost.
+//!
+//! A [`Bridge`] fetches a feed, converts entries it hasn't seen before into posts with a
+//! [`Template`], and publishes them with [`Session::create_post`]. The IDs of entries that have
+//! been handled are kept in a JSON state file, so entries are only posted once across restarts,
+//! along with how many times each entry that failed to publish has been attempted. Before an
+//! entry is attempted again, the project's recent posts are checked for one created by an earlier
+//! attempt whose response never arrived.
+//!
+//! ```no_run
+//! use eggbug::{bridge::Bridge, Session};
+//!
+//! # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+//! let session = Session::login("eggbug@website.invalid", "hunter2").await?;
+//! let mut bridge = Bridge::open(
+//!     "https://blog.example.invalid/feed.xml",
+//!     "eggbug",
+//!     "bridge-state.json",
+//! )
+//! .await?;
+//! // Don't post the entries that are already in the feed the first time the bridge runs.
+//! if bridge.is_empty() {
+//!     bridge.catch_up(&session).await?;
+//! }
+//! for (id, result) in bridge.run_once(&session).await? {
+//!     println!("{}: {:?}", id, result);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::attachment::guess_content_type;
+use crate::persist::save_json;
+use crate::post::MAX_HEADLINE_LENGTH;
+use crate::{Attachment, Error, IdempotencyKey, MediaMetadata, Post, PostId, Session};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The number of attempts made to publish an entry before giving up, unless changed with
+/// [`Bridge::with_max_attempts`].
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// A feed entry, simplified from whatever RSS or Atom elements it was parsed from.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Entry {
+    /// The entry's ID (its `guid` or `id`). Entries without one are given an ID derived from
+    /// their contents.
+    pub id: String,
+    /// The entry's title.
+    pub title: Option<String>,
+    /// The URL of the entry on the original site.
+    pub link: Option<String>,
+    /// The entry's HTML content, or its summary if it has no content.
+    pub content: Option<String>,
+    /// The entry's categories.
+    pub categories: Vec<String>,
+    /// When the entry was published, or last updated if it has no publication date.
+    pub published: Option<DateTime<Utc>>,
+    /// Images enclosed with the entry.
+    pub images: Vec<Image>,
+}
+
+/// An image enclosed with a feed [`Entry`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Image {
+    /// The image's URL.
+    pub url: String,
+    /// The image's content type.
+    pub content_type: String,
+    /// A description of the image, used as its alt text.
+    pub description: Option<String>,
+}
+
+/// Hooks for converting feed entries into posts.
+///
+/// Every method has a default; override the ones you want to change. [`DefaultTemplate`] uses
+/// all of the defaults.
+pub trait Template: Send + Sync {
+    /// Returns false if the entry should be skipped. Skipped entries are still marked as seen.
+    fn include(&self, _entry: &Entry) -> bool {
+        true
+    }
+
+    /// Returns the post's headline. By default, this is the entry's title. Headlines longer than
+    /// cohost allows are cut short.
+    fn headline(&self, entry: &Entry) -> String {
+        entry.title.clone().unwrap_or_default()
+    }
+
+    /// Returns the post's markdown. By default, this is the entry's content followed by a link to
+    /// the entry.
+    fn markdown(&self, entry: &Entry) -> String {
+        let mut markdown = entry
+            .content
+            .as_deref()
+            .unwrap_or_default()
+            .trim()
+            .to_owned();
+        if let Some(link) = &entry.link {
+            if !markdown.is_empty() {
+                markdown.push_str("\n\n");
+            }
+            write!(markdown, "[{}]({})", link, link).unwrap();
+        }
+        markdown
+    }
+
+    /// Returns the post's tags. By default, these are the entry's categories.
+    fn tags(&self, entry: &Entry) -> Vec<String> {
+        entry.categories.clone()
+    }
+
+    /// Makes any other changes to the post before it is published, such as setting content
+    /// warnings. Attachments for the entry's images have already been added, except for images
+    /// that couldn't be downloaded or that cohost wouldn't accept.
+    fn finish(&self, _entry: &Entry, _post: &mut Post) {}
+}
+
+/// The [`Template`] with every default hook.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultTemplate;
+
+impl Template for DefaultTemplate {}
+
+/// Parses an RSS or Atom feed, returning its entries in the order they appear.
+pub fn parse(data: &[u8]) -> Result<Vec<Entry>, Error> {
+    let feed = feed_rs::parser::parse(data)?;
+    Ok(feed.entries.into_iter().map(Entry::from).collect())
+}
+
+impl From<feed_rs::model::Entry> for Entry {
+    fn from(entry: feed_rs::model::Entry) -> Entry {
+        let mut images = Vec::new();
+        for object in &entry.media {
+            let description = object
+                .description
+                .as_ref()
+                .or(object.title.as_ref())
+                .map(|text| text.content.clone());
+            for content in &object.content {
+                if let Some(url) = &content.url {
+                    let content_type = content
+                        .content_type
+                        .as_ref()
+                        .map_or_else(|| guess_content_type(url.path()), ToString::to_string);
+                    push_image(&mut images, url.as_str(), content_type, description.clone());
+                }
+            }
+        }
+        for link in &entry.links {
+            if link.rel.as_deref() == Some("enclosure") {
+                let content_type = link
+                    .media_type
+                    .clone()
+                    .unwrap_or_else(|| guess_content_type(strip_query(&link.href)));
+                push_image(&mut images, &link.href, content_type, link.title.clone());
+            }
+        }
+
+        let link = entry
+            .links
+            .iter()
+            .find(|link| matches!(link.rel.as_deref(), None | Some("alternate")))
+            .map(|link| link.href.clone());
+        let content = entry
+            .content
+            .and_then(|content| content.body)
+            .or_else(|| entry.summary.map(|summary| summary.content));
+
+        Entry {
+            id: entry.id,
+            title: entry.title.map(|title| title.content),
+            link,
+            content,
+            categories: entry
+                .categories
+                .into_iter()
+                .map(|category| category.label.unwrap_or(category.term))
+                .collect(),
+            published: entry.published.or(entry.updated),
+            images,
+        }
+    }
+}
+
+fn push_image(
+    images: &mut Vec<Image>,
+    url: &str,
+    content_type: String,
+    description: Option<String>,
+) {
+    if content_type.starts_with("image/") && images.iter().all(|image| image.url != url) {
+        images.push(Image {
+            url: url.to_owned(),
+            content_type,
+            description,
+        });
+    }
+}
+
+fn strip_query(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+/// Cuts `text` short to at most `max` characters, ending it with an ellipsis if anything was cut.
+fn truncate(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+    let mut truncated = text
+        .chars()
+        .take(max - 1)
+        .collect::<String>()
+        .trim_end()
+        .to_owned();
+    truncated.push('…');
+    truncated
+}
+
+/// A feed entry that was published by a [`Bridge`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Published {
+    /// The ID of the entry's post.
+    pub post_id: PostId,
+    /// The URLs of the entry's images that were left out of the post because they couldn't be
+    /// downloaded or cohost wouldn't accept them.
+    pub skipped_images: Vec<String>,
+}
+
+/// Mirrors a feed onto a project. See the [module documentation][self] for an example.
+pub struct Bridge {
+    feed_url: String,
+    project: String,
+    path: PathBuf,
+    template: Box<dyn Template>,
+    max_attempts: u32,
+    state: State,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct State {
+    seen: BTreeSet<String>,
+    /// The number of failed attempts to publish each entry that hasn't been given up on yet.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    attempts: BTreeMap<String, u32>,
+    /// When the first attempt to publish each entry started, kept until the entry is published or
+    /// given up on.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    sending: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl fmt::Debug for Bridge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bridge")
+            .field("feed_url", &self.feed_url)
+            .field("project", &self.project)
+            .field("path", &self.path)
+            .field("max_attempts", &self.max_attempts)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Bridge {
+    /// Opens a bridge from the feed at `feed_url` to `project`, with its state file at `path`. If
+    /// the state file doesn't exist, no entries have been seen.
+    pub async fn open(
+        feed_url: impl Into<String>,
+        project: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Result<Bridge, Error> {
+        let path = path.as_ref().to_owned();
+        let state = match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Bridge {
+            feed_url: feed_url.into(),
+            project: project.into(),
+            path,
+            template: Box::new(DefaultTemplate),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            state,
+        })
+    }
+
+    /// Sets the template used to convert entries into posts, in a builder-style function.
+    #[must_use]
+    pub fn with_template(self, template: impl Template + 'static) -> Bridge {
+        Bridge {
+            template: Box::new(template),
+            ..self
+        }
+    }
+
+    /// Sets the number of attempts made to publish an entry before giving up, in a builder-style
+    /// function.
+    #[must_use]
+    pub fn with_max_attempts(self, max_attempts: u32) -> Bridge {
+        Bridge {
+            max_attempts,
+            ..self
+        }
+    }
+
+    /// Returns true if no entries have been seen yet, such as when the state file was just
+    /// created.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.state.seen.is_empty()
+    }
+
+    /// Returns true if the entry with this ID has been seen.
+    #[must_use]
+    pub fn is_seen(&self, id: &str) -> bool {
+        self.state.seen.contains(id)
+    }
+
+    /// Fetches and parses the feed.
+    pub async fn fetch(&self, session: &Session) -> Result<Vec<Entry>, Error> {
+        let data = session
+            .client
+            .request_url(reqwest::Method::GET, &self.feed_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        parse(&data)
+    }
+
+    /// Returns the entries that haven't been seen yet, oldest first.
+    #[must_use]
+    pub fn new_entries(&self, mut entries: Vec<Entry>) -> Vec<Entry> {
+        entries.retain(|entry| !self.is_seen(&entry.id));
+        // Feeds are usually newest first; reverse them so entries without dates stay in order.
+        entries.reverse();
+        entries.sort_by_key(|entry| entry.published);
+        entries
+    }
+
+    /// Converts an entry into a post with the bridge's template, without any attachments.
+    pub fn to_post(&self, entry: &Entry) -> Post {
+        Post {
+            headline: truncate(self.template.headline(entry), MAX_HEADLINE_LENGTH),
+            markdown: self.template.markdown(entry),
+            tags: self.template.tags(entry),
+            ..Post::default()
+        }
+    }
+
+    /// Marks every entry currently in the feed as seen without posting them. This is useful the
+    /// first time a bridge runs, to only mirror entries published from then on.
+    pub async fn catch_up(&mut self, session: &Session) -> Result<(), Error> {
+        let entries = self.fetch(session).await?;
+        self.state
+            .seen
+            .extend(entries.into_iter().map(|entry| entry.id));
+        self.save().await
+    }
+
+    /// Fetches the feed and publishes every entry that hasn't been seen yet, oldest first.
+    ///
+    /// Returns the IDs and results of the entries that were attempted. Images that can't be
+    /// downloaded or that cohost wouldn't accept are left out of their posts and listed in
+    /// [`Published::skipped_images`]. Entries that fail to publish are attempted again the next
+    /// time, until they have been attempted too many times; entries whose posts are rejected as
+    /// invalid aren't retried. Entries that are given up on are marked as seen. Errors fetching
+    /// the feed or writing the state file are returned immediately.
+    #[tracing::instrument(skip(self, session), fields(feed_url = %self.feed_url))]
+    #[allow(clippy::type_complexity)]
+    pub async fn run_once(
+        &mut self,
+        session: &Session,
+    ) -> Result<Vec<(String, Result<Published, Error>)>, Error> {
+        let entries = self.fetch(session).await?;
+        self.publish_new(session, entries).await
+    }
+
+    /// Publishes new entries as they appear, checking the feed every `interval`.
+    ///
+    /// Errors fetching the feed are logged and retried after `interval`. This only returns if
+    /// writing the state file fails.
+    #[cfg(feature = "poll")]
+    pub async fn run(
+        &mut self,
+        session: &Session,
+        interval: std::time::Duration,
+    ) -> Result<(), Error> {
+        loop {
+            match self.fetch(session).await {
+                Ok(entries) => {
+                    self.publish_new(session, entries).await?;
+                }
+                Err(err) => tracing::warn!(%err, "failed to check feed"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Publishes the entries in `entries` that haven't been seen yet. Only errors writing the
+    /// state file are returned.
+    #[allow(clippy::type_complexity)]
+    async fn publish_new(
+        &mut self,
+        session: &Session,
+        entries: Vec<Entry>,
+    ) -> Result<Vec<(String, Result<Published, Error>)>, Error> {
+        let entries = self.new_entries(entries);
+        let mut results = Vec::new();
+        for entry in entries {
+            if !self.template.include(&entry) {
+                tracing::info!(id = %entry.id, "skipping feed entry");
+                self.state.seen.insert(entry.id);
+                self.save().await?;
+                continue;
+            }
+
+            // Note when the first attempt started before sending anything, so that if the response
+            // never arrives, the post can be looked for instead of being created again.
+            let since = self.state.sending.get(&entry.id).copied();
+            if since.is_none() {
+                self.state.sending.insert(entry.id.clone(), Utc::now());
+                self.save().await?;
+            }
+
+            let result = self.publish(session, &entry, since).await;
+            match &result {
+                Ok(published) => {
+                    tracing::info!(
+                        id = %entry.id,
+                        post_id = %published.post_id,
+                        "published feed entry"
+                    );
+                    self.state.attempts.remove(&entry.id);
+                    self.state.sending.remove(&entry.id);
+                    self.state.seen.insert(entry.id.clone());
+                }
+                Err(err) => {
+                    let attempts = self.state.attempts.entry(entry.id.clone()).or_default();
+                    *attempts += 1;
+                    if *attempts >= self.max_attempts || err.is_permanent() {
+                        tracing::warn!(id = %entry.id, %err, "giving up on feed entry");
+                        self.state.attempts.remove(&entry.id);
+                        self.state.sending.remove(&entry.id);
+                        self.state.seen.insert(entry.id.clone());
+                    } else {
+                        tracing::warn!(id = %entry.id, %err, "failed to publish feed entry");
+                    }
+                }
+            }
+            self.save().await?;
+            results.push((entry.id, result));
+        }
+        Ok(results)
+    }
+
+    /// Publishes an entry. If an earlier attempt started at `since`, the project's posts since
+    /// then are checked for one it created first.
+    async fn publish(
+        &self,
+        session: &Session,
+        entry: &Entry,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Published, Error> {
+        let mut post = self.to_post(entry);
+        let mut skipped_images = Vec::new();
+        for image in &entry.images {
+            let err = match download_image(session, image).await {
+                Ok(attachment) => {
+                    let errors = attachment.validate(post.attachments.len());
+                    if errors.is_empty() {
+                        post.attachments.push(attachment);
+                        continue;
+                    }
+                    Error::Validation(errors)
+                }
+                Err(err) => err,
+            };
+            tracing::warn!(url = %image.url, %err, "skipping image");
+            skipped_images.push(image.url.clone());
+        }
+        self.template.finish(entry, &mut post);
+
+        if let Some(since) = since {
+            let key = IdempotencyKey::for_post(&post);
+            let found =
+                crate::idempotency::find_post(session, &self.project, key, since, Some(&post))
+                    .await?;
+            if let Some(post_id) = found {
+                tracing::info!(id = %entry.id, %post_id, "found post created by an earlier attempt");
+                return Ok(Published {
+                    post_id,
+                    skipped_images,
+                });
+            }
+        }
+        let post_id = session.create_post(&self.project, &mut post).await?;
+        Ok(Published {
+            post_id,
+            skipped_images,
+        })
+    }
+
+    async fn save(&self) -> Result<(), Error> {
+        save_json(&self.path, &self.state).await
+    }
+}
+
+async fn download_image(session: &Session, image: &Image) -> Result<Attachment, Error> {
+    let content = session
+        .client
+        .request_url(reqwest::Method::GET, &image.url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let filename = strip_query(&image.url)
+        .rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .unwrap_or("image")
+        .to_owned();
+    let attachment = Attachment::new(
+        content,
+        filename,
+        image.content_type.clone(),
+        MediaMetadata::Image {
+            width: None,
+            height: None,
+        },
+    );
+    Ok(attachment.with_alt_text(image.description.clone().unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Bridge, Entry, Template};
+    use crate::persist::temp_path;
+    use crate::test_util::{posts_page, session, PNG};
+    use crate::{Error, MemoryTransport, Post, PostId};
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_rss() -> Result<(), Box<dyn std::error::Error>> {
+        let entries = parse(include_bytes!("../samples/bridge.rss.xml"))?;
+        assert_eq!(entries.len(), 2);
+        let entry = &entries[0];
+        assert_eq!(entry.id, "https://blog.example.invalid/second");
+        assert_eq!(entry.title.as_deref(), Some("Second post"));
+        assert_eq!(
+            entry.link.as_deref(),
+            Some("https://blog.example.invalid/second")
+        );
+        assert_eq!(
+            entry.content.as_deref(),
+            Some("<p>The full text of the <em>second</em> post.</p>")
+        );
+        assert_eq!(entry.categories, ["bugs", "eggs"]);
+        assert_eq!(entry.images.len(), 1);
+        assert_eq!(
+            entry.images[0].url,
+            "https://blog.example.invalid/images/egg.png"
+        );
+        assert_eq!(entry.images[0].content_type, "image/png");
+        // Audio enclosures aren't images.
+        assert!(entries[1].images.is_empty());
+        assert_eq!(entries[1].content.as_deref(), Some("Hello, world!"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_atom() -> Result<(), Box<dyn std::error::Error>> {
+        let entries = parse(include_bytes!("../samples/bridge.atom.xml"))?;
+        assert_eq!(entries.len(), 2);
+        let entry = &entries[0];
+        assert_eq!(entry.id, "tag:blog.example.invalid,2022:second");
+        assert_eq!(
+            entry.link.as_deref(),
+            Some("https://blog.example.invalid/second")
+        );
+        assert_eq!(entry.categories, ["bugs", "Eggs"]);
+        assert_eq!(entry.images.len(), 1);
+        assert_eq!(entry.images[0].content_type, "image/jpeg");
+        assert_eq!(
+            entry.images[0].description.as_deref(),
+            Some("a speckled egg")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bridge() -> Result<(), Box<dyn std::error::Error>> {
+        struct Shouty;
+
+        impl Template for Shouty {
+            fn include(&self, entry: &Entry) -> bool {
+                !entry.categories.is_empty()
+            }
+
+            fn headline(&self, entry: &Entry) -> String {
+                entry.title.as_deref().unwrap_or_default().to_uppercase()
+            }
+
+            fn tags(&self, entry: &Entry) -> Vec<String> {
+                let mut tags = entry.categories.clone();
+                tags.push("automated post".into());
+                tags
+            }
+        }
+
+        let path = temp_path("bridge.json");
+        let _ = std::fs::remove_file(&path);
+        let entries = parse(include_bytes!("../samples/bridge.rss.xml"))?;
+
+        let mut bridge = Bridge::open("https://blog.example.invalid/feed.xml", "eggbug", &path)
+            .await?
+            .with_template(Shouty);
+        assert!(bridge.is_empty());
+        let new = bridge.new_entries(entries.clone());
+        assert_eq!(
+            new.iter()
+                .map(|entry| entry.id.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "https://blog.example.invalid/first",
+                "https://blog.example.invalid/second"
+            ]
+        );
+        assert!(!bridge.template.include(&new[0]));
+
+        let post = bridge.to_post(&new[1]);
+        assert_eq!(post.headline, "SECOND POST");
+        assert_eq!(
+            post.markdown,
+            "<p>The full text of the <em>second</em> post.</p>\n\n\
+             [https://blog.example.invalid/second](https://blog.example.invalid/second)"
+        );
+        assert_eq!(post.tags, ["bugs", "eggs", "automated post"]);
+        assert!(post.attachments.is_empty());
+        assert!(!post.is_empty());
+
+        let post = bridge.to_post(&Entry {
+            title: Some("egg ".repeat(50)),
+            categories: vec!["eggs".into()],
+            ..Entry::default()
+        });
+        assert_eq!(post.headline.chars().count(), 140);
+        assert!(post.headline.ends_with("EGG…"));
+
+        bridge
+            .state
+            .seen
+            .insert("https://blog.example.invalid/first".into());
+        bridge.save().await?;
+
+        let bridge = Bridge::open("https://blog.example.invalid/feed.xml", "eggbug", &path).await?;
+        assert!(bridge.is_seen("https://blog.example.invalid/first"));
+        let new = bridge.new_entries(entries);
+        assert_eq!(new.len(), 1);
+        assert_eq!(new[0].id, "https://blog.example.invalid/second");
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_once_gives_up() -> Result<(), Box<dyn std::error::Error>> {
+        struct BadTags;
+
+        impl Template for BadTags {
+            fn finish(&self, entry: &Entry, post: &mut Post) {
+                if !entry.images.is_empty() {
+                    post.tags.push(String::new());
+                }
+            }
+        }
+
+        let path = temp_path("bridge-give-up.json");
+        let _ = std::fs::remove_file(&path);
+        let transport = MemoryTransport::new();
+        transport.respond(
+            "GET",
+            "feed.xml",
+            200,
+            &include_bytes!("../samples/bridge.rss.xml")[..],
+        );
+        transport.respond("GET", "images/egg.png", 200, PNG);
+        transport.respond("POST", "project/eggbug/posts", 500, "oops");
+        transport.respond_json(
+            "GET",
+            "project/eggbug/posts",
+            200,
+            &json!({ "nItems": 0, "nPages": 0, "items": [] }),
+        );
+        let session = session(&transport);
+
+        let mut bridge = Bridge::open("https://blog.example.invalid/feed.xml", "eggbug", &path)
+            .await?
+            .with_template(BadTags)
+            .with_max_attempts(2);
+
+        // The invalid post is given up on immediately; the server error is retried.
+        let results = bridge.run_once(&session).await?;
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[1].1, Err(Error::Validation(_))));
+        assert!(!bridge.is_seen("https://blog.example.invalid/first"));
+        assert!(bridge.is_seen("https://blog.example.invalid/second"));
+
+        let mut bridge = Bridge::open("https://blog.example.invalid/feed.xml", "eggbug", &path)
+            .await?
+            .with_template(BadTags)
+            .with_max_attempts(2);
+        assert_eq!(
+            bridge
+                .state
+                .attempts
+                .get("https://blog.example.invalid/first"),
+            Some(&1)
+        );
+        let results = bridge.run_once(&session).await?;
+        assert_eq!(results.len(), 1);
+        assert!(bridge.is_seen("https://blog.example.invalid/first"));
+        assert!(bridge.state.attempts.is_empty());
+        assert!(bridge.state.sending.is_empty());
+        assert!(bridge.run_once(&session).await?.is_empty());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_once_skips_images() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("bridge-skip-images.json");
+        let _ = std::fs::remove_file(&path);
+        let transport = MemoryTransport::new();
+        transport.respond(
+            "GET",
+            "feed.xml",
+            200,
+            &include_bytes!("../samples/bridge.rss.xml")[..],
+        );
+        transport.respond("GET", "images/egg.png", 404, "");
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":1}"#);
+        let session = session(&transport);
+
+        let mut bridge =
+            Bridge::open("https://blog.example.invalid/feed.xml", "eggbug", &path).await?;
+        let results = bridge.run_once(&session).await?;
+        assert_eq!(results.len(), 2);
+        let published = results[1].1.as_ref().unwrap();
+        assert_eq!(published.post_id, PostId(1));
+        assert_eq!(
+            published.skipped_images,
+            ["https://blog.example.invalid/images/egg.png"]
+        );
+        // The post is published without the image.
+        let last: serde_json::Value = transport.requests().last().unwrap().json()?;
+        assert_eq!(last["postState"], 1);
+        assert!(last["blocks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|block| block["type"] != "attachment"));
+        assert!(bridge.is_seen("https://blog.example.invalid/second"));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_once_finds_post() -> Result<(), Box<dyn std::error::Error>> {
+        struct NoImages;
+
+        impl Template for NoImages {
+            fn include(&self, entry: &Entry) -> bool {
+                entry.images.is_empty()
+            }
+        }
+
+        let path = temp_path("bridge-find-post.json");
+        let _ = std::fs::remove_file(&path);
+        let entries = parse(include_bytes!("../samples/bridge.rss.xml"))?;
+        let transport = MemoryTransport::new();
+        transport.respond(
+            "GET",
+            "feed.xml",
+            200,
+            &include_bytes!("../samples/bridge.rss.xml")[..],
+        );
+        // The post is created, but the response never arrives.
+        transport.respond("POST", "project/eggbug/posts", 502, "");
+        let session = session(&transport);
+
+        let mut bridge = Bridge::open("https://blog.example.invalid/feed.xml", "eggbug", &path)
+            .await?
+            .with_template(NoImages);
+        let results = bridge.run_once(&session).await?;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].1.is_err());
+        assert!(bridge
+            .state
+            .sending
+            .contains_key("https://blog.example.invalid/first"));
+
+        let post = bridge.to_post(&entries[1]);
+        transport.respond_json("GET", "project/eggbug/posts", 200, &posts_page(7, &post));
+        let mut bridge = Bridge::open("https://blog.example.invalid/feed.xml", "eggbug", &path)
+            .await?
+            .with_template(NoImages);
+        let results = bridge.run_once(&session).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1.as_ref().unwrap().post_id, PostId(7));
+        assert!(bridge.is_seen("https://blog.example.invalid/first"));
+        assert!(bridge.state.sending.is_empty());
+        // Only the first attempt created a post.
+        assert_eq!(
+            transport
+                .requests()
+                .iter()
+                .filter(|request| request.method == "POST")
+                .count(),
+            1
+        );
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{Error, Post, PostId, Session};
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// The maximum number of projects [`Session::create_post_multi`] creates posts on at once.
+const CREATE_MULTI_CONCURRENCY: usize = 4;
+
+/// Selects posts for bulk operations such as [`Session::bulk_delete`] and
+/// [`Session::bulk_edit`].
+///
+/// A post matches if it matches every criterion that is set. The default filter matches every
+/// post.
+#[derive(Clone, Debug, Default)]
+pub struct PostFilter {
+    /// Only match posts with this tag.
+    pub tag: Option<String>,
+    /// Only match posts published at or after this time.
+    pub published_after: Option<DateTime<Utc>>,
+    /// Only match posts published before this time.
+    pub published_before: Option<DateTime<Utc>>,
+    /// Only match posts that are (if `true`) or aren't (if `false`) drafts.
+    pub draft: Option<bool>,
+    /// Only match posts with headlines matching this regular expression.
+    pub headline: Option<Regex>,
+}
+
+impl PostFilter {
+    /// Returns true if `post` matches this filter.
+    ///
+    /// Date criteria only match posts with [`metadata`][`Post::metadata`], as returned by the API.
+    #[must_use]
+    // `Option::is_none_or` needs Rust 1.82.
+    #[allow(unknown_lints, clippy::unnecessary_map_or)]
+    pub fn matches(&self, post: &Post) -> bool {
+        let publication_date = post
+            .metadata
+            .as_ref()
+            .map(|metadata| metadata.publication_date);
+        self.tag
+            .as_ref()
+            .map_or(true, |tag| post.tags.iter().any(|t| t == tag))
+            && self.published_after.map_or(true, |after| {
+                publication_date.is_some_and(|date| date >= after)
+            })
+            && self.published_before.map_or(true, |before| {
+                publication_date.is_some_and(|date| date < before)
+            })
+            && self.draft.map_or(true, |draft| post.draft == draft)
+            && self
+                .headline
+                .as_ref()
+                .map_or(true, |headline| headline.is_match(&post.headline))
+    }
+}
+
+/// Options for bulk operations.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BulkOptions {
+    /// The maximum number of posts to delete or edit at once.
+    pub concurrency: usize,
+    /// If true, only find the matching posts; don't delete or edit anything.
+    pub dry_run: bool,
+}
+
+impl Default for BulkOptions {
+    fn default() -> BulkOptions {
+        BulkOptions {
+            concurrency: 4,
+            dry_run: false,
+        }
+    }
+}
+
+/// Summary of a bulk operation.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct BulkReport {
+    /// Posts that matched the filter.
+    pub matched: Vec<PostId>,
+    /// Posts that were successfully deleted or edited. Empty for a dry run.
+    pub succeeded: Vec<PostId>,
+    /// Posts that failed to be deleted or edited, and why.
+    pub failed: Vec<(PostId, Error)>,
+}
+
+/// Fetches every post on the project that matches `filter`.
+async fn matching_posts(
+    session: &Session,
+    project: &str,
+    filter: &PostFilter,
+) -> Result<Vec<Post>, Error> {
+    // Collect every match before changing anything, since deleting posts shifts later pages.
+    crate::page::paginate(|page| session.client.get_posts_page(project, page))
+        .try_filter(|post| futures::future::ready(filter.matches(post)))
+        .try_collect()
+        .await
+}
+
+fn post_id(post: &Post) -> Option<PostId> {
+    post.metadata.as_ref().map(|metadata| metadata.locations.id)
+}
+
+pub(crate) async fn delete(
+    session: &Session,
+    project: &str,
+    filter: &PostFilter,
+    options: BulkOptions,
+) -> Result<BulkReport, Error> {
+    let matched = matching_posts(session, project, filter)
+        .await?
+        .iter()
+        .filter_map(post_id)
+        .collect::<Vec<_>>();
+    tracing::info!(matched = matched.len(), dry_run = options.dry_run);
+    if options.dry_run {
+        return Ok(BulkReport {
+            matched,
+            ..BulkReport::default()
+        });
+    }
+
+    let results = futures::stream::iter(matched.iter().copied())
+        .map(|id| async move { (id, session.delete_post(project, id).await) })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    Ok(BulkReport::from_results(matched, results))
+}
+
+pub(crate) async fn edit(
+    session: &Session,
+    project: &str,
+    filter: &PostFilter,
+    options: BulkOptions,
+    mut edit: impl FnMut(&mut Post),
+) -> Result<BulkReport, Error> {
+    let mut posts = matching_posts(session, project, filter)
+        .await?
+        .into_iter()
+        .filter_map(|post| Some((post_id(&post)?, post)))
+        .collect::<Vec<_>>();
+    let matched = posts.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    tracing::info!(matched = matched.len(), dry_run = options.dry_run);
+    if options.dry_run {
+        return Ok(BulkReport {
+            matched,
+            ..BulkReport::default()
+        });
+    }
+
+    for (_, post) in &mut posts {
+        edit(post);
+    }
+    let results = futures::stream::iter(posts)
+        .map(|(id, mut post)| async move {
+            let result = session.edit_post(project, id, &mut post).await;
+            (id, result.map(drop))
+        })
+        .buffer_unordered(options.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    Ok(BulkReport::from_results(matched, results))
+}
+
+pub(crate) async fn create_multi(
+    session: &Session,
+    projects: &[&str],
+    post: &Post,
+) -> HashMap<String, Result<PostId, Error>> {
+    let mut unique = projects.to_vec();
+    unique.sort_unstable();
+    unique.dedup();
+    futures::stream::iter(unique)
+        .map(|project| async move {
+            // Each project needs its own copy of the attachments uploaded.
+            let mut post = post.clone();
+            let result = session.create_post(project, &mut post).await;
+            if let Err(err) = &result {
+                tracing::warn!(project, %err, "failed to create post");
+            }
+            (project.to_owned(), result)
+        })
+        .buffer_unordered(CREATE_MULTI_CONCURRENCY)
+        .collect()
+        .await
+}
+
+impl BulkReport {
+    fn from_results(matched: Vec<PostId>, results: Vec<(PostId, Result<(), Error>)>) -> BulkReport {
+        let mut report = BulkReport {
+            matched,
+            ..BulkReport::default()
+        };
+        for (id, result) in results {
+            match result {
+                Ok(()) => report.succeeded.push(id),
+                Err(err) => {
+                    tracing::warn!(%id, %err, "bulk operation failed");
+                    report.failed.push((id, err));
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{post_id, PostFilter};
+    use crate::{Post, PostId};
+    use chrono::Utc;
+    use regex::Regex;
+
+    #[test]
+    fn test_post_filter() -> Result<(), Box<dyn std::error::Error>> {
+        use chrono::TimeZone;
+
+        let post_page: crate::post::PostPage =
+            serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+        let posts = Vec::<Post>::from(post_page);
+        let matching = |filter: &PostFilter| {
+            posts
+                .iter()
+                .filter(|post| filter.matches(post))
+                .filter_map(post_id)
+                .map(|id| id.0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching(&PostFilter::default()).len(), posts.len());
+        assert_eq!(
+            matching(&PostFilter {
+                headline: Some(Regex::new("(?i)^this is a test")?),
+                ..PostFilter::default()
+            }),
+            vec![185_838]
+        );
+        assert_eq!(
+            matching(&PostFilter {
+                tag: Some("test tag one".into()),
+                ..PostFilter::default()
+            }),
+            vec![185_838]
+        );
+        assert_eq!(
+            matching(&PostFilter {
+                published_after: Some(Utc.with_ymd_and_hms(2022, 11, 4, 3, 28, 0).unwrap()),
+                ..PostFilter::default()
+            }),
+            vec![185_922, 185_916]
+        );
+        assert_eq!(
+            matching(&PostFilter {
+                published_before: Some(Utc.timestamp_opt(1_667_531_869, 0).unwrap()),
+                ..PostFilter::default()
+            }),
+            Vec::<u64>::new()
+        );
+        assert_eq!(
+            matching(&PostFilter {
+                draft: Some(true),
+                ..PostFilter::default()
+            }),
+            Vec::<u64>::new()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_multi() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::test_util::{mock_attachment_upload, png, session};
+        use crate::MemoryTransport;
+        use serde_json::json;
+
+        let transport = MemoryTransport::new();
+        transport.respond("POST", "project/two/posts", 500, "oops");
+        for (project, id) in [("one", 1), ("three", 3)] {
+            let post_id = json!({ "postId": id });
+            transport.respond_json("POST", &format!("project/{}/posts", project), 200, &post_id);
+            mock_attachment_upload(&transport, project, id);
+            transport.respond_json(
+                "PUT",
+                &format!("project/{}/posts/{}", project, id),
+                200,
+                &post_id,
+            );
+        }
+        let session = session(&transport);
+
+        let post = Post {
+            headline: "announcement".into(),
+            attachments: vec![png()],
+            ..Post::default()
+        };
+        let results = session
+            .create_post_multi(&["one", "two", "three", "one"], &post)
+            .await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(*results["one"].as_ref().unwrap(), PostId(1));
+        assert!(results["two"].is_err());
+        assert_eq!(*results["three"].as_ref().unwrap(), PostId(3));
+
+        // The attachment is uploaded once for each project the post was created on.
+        let requests = transport.requests();
+        let mut uploaded = requests
+            .iter()
+            .filter(|request| request.url.ends_with("trpc/posts.attachment.start"))
+            .map(|request| {
+                let body: serde_json::Value = request.json()?;
+                Ok(body["projectHandle"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_owned())
+            })
+            .collect::<Result<Vec<_>, crate::Error>>()?;
+        uploaded.sort_unstable();
+        assert_eq!(uploaded, ["one", "three"]);
+        let uploads = requests
+            .iter()
+            .filter(|request| request.url == "https://uploads.example.invalid/bucket")
+            .count();
+        assert_eq!(uploads, 2);
+        // The post itself is left as it was, ready to be created again.
+        assert!(post.attachments[0].is_new());
+        Ok(())
+    }
+}
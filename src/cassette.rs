@@ -0,0 +1,359 @@
+use crate::persist::save_json;
+use crate::transport::Transport;
+use crate::Error;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Fields removed from recorded JSON bodies and query strings.
+const REDACTED_FIELDS: &[&str] = &["clientHash", "email", "password"];
+
+/// Fields whose values are objects with every value redacted, keeping their keys so the bodies
+/// still parse when replayed. Attachment uploads are signed with `requiredFields`.
+const REDACTED_MAP_FIELDS: &[&str] = &["requiredFields"];
+
+/// What redacted values are replaced with.
+const REDACTED: &str = "[redacted]";
+
+/// The contents of a cassette file: request and response pairs, in the order they were made.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Interaction {
+    request: CassetteRequest,
+    response: CassetteResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CassetteRequest {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CassetteResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    body: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    base64: bool,
+}
+
+/// A [`Transport`] that passes requests to another transport and records each request and
+/// response to a cassette file, for replaying later with [`ReplayTransport`].
+///
+/// The file is rewritten after every response, so it is complete even if the process stops
+/// early. Only the method, URL, and body of requests are recorded, and only the status, content
+/// type, and body of responses, so `Cookie`, `Set-Cookie`, and other headers are never saved.
+/// Emails, password hashes, passwords, and the fields that sign attachment uploads are replaced
+/// with `[redacted]` in URLs and JSON bodies.
+///
+/// Use [`Client::with_recording`][`crate::Client::with_recording`] to record a client's current
+/// transport.
+#[derive(Debug)]
+pub struct RecordTransport {
+    inner: Arc<dyn Transport>,
+    path: PathBuf,
+    cassette: tokio::sync::Mutex<Cassette>,
+}
+
+impl RecordTransport {
+    /// Creates a transport that sends requests with `inner` and records them to a new cassette
+    /// file at `path`, replacing any file already there.
+    pub fn new(inner: impl Transport + 'static, path: impl AsRef<Path>) -> RecordTransport {
+        RecordTransport::from_arc(Arc::new(inner), path)
+    }
+
+    pub(crate) fn from_arc(inner: Arc<dyn Transport>, path: impl AsRef<Path>) -> RecordTransport {
+        RecordTransport {
+            inner,
+            path: path.as_ref().to_owned(),
+            cassette: tokio::sync::Mutex::default(),
+        }
+    }
+
+    async fn record(
+        &self,
+        request: CassetteRequest,
+        response: Response,
+    ) -> Result<Response, Error> {
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let body = response.bytes().await?;
+
+        let (recorded_body, base64) = match std::str::from_utf8(&body) {
+            Ok(text) => (redact_body(text), false),
+            Err(_) => (base64::encode(&body), true),
+        };
+        let mut cassette = self.cassette.lock().await;
+        cassette.interactions.push(Interaction {
+            request,
+            response: CassetteResponse {
+                status: status.as_u16(),
+                content_type: content_type.clone(),
+                body: recorded_body,
+                base64,
+            },
+        });
+        save_json(&self.path, &*cassette).await?;
+        drop(cassette);
+
+        Ok(build_response(status, content_type.as_deref(), body))
+    }
+}
+
+impl Transport for RecordTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        Box::pin(async move {
+            let recorded = CassetteRequest::from(&request);
+            let response = self.inner.execute(request).await?;
+            self.record(recorded, response).await
+        })
+    }
+}
+
+/// A [`Transport`] that answers requests from a cassette file written by [`RecordTransport`],
+/// without any network access.
+///
+/// Each request is answered with the first recorded response to a request with the same method
+/// and URL that hasn't been used yet. Once every matching response has been used, the last one
+/// is used again. Requests that match nothing get an empty 404 response.
+///
+/// ```no_run
+/// use eggbug::{Client, ReplayTransport};
+///
+/// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = Client::new().with_transport(ReplayTransport::open("tests/posts.json").await?);
+/// let posts = client.get_posts_page("eggbug", 0).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReplayTransport {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayTransport {
+    /// Loads the cassette file at `path`.
+    pub async fn open(path: impl AsRef<Path>) -> Result<ReplayTransport, Error> {
+        let cassette: Cassette = serde_json::from_slice(&tokio::fs::read(path).await?)?;
+        Ok(ReplayTransport {
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            interactions: cassette.interactions,
+        })
+    }
+
+    fn find(&self, request: &CassetteRequest) -> Option<&CassetteResponse> {
+        let mut used = self.used.lock().unwrap();
+        let matching = self
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| {
+                interaction.request.method == request.method
+                    && interaction.request.url == request.url
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let index = matching
+            .iter()
+            .copied()
+            .find(|index| !used[*index])
+            .or_else(|| matching.last().copied())?;
+        used[index] = true;
+        Some(&self.interactions[index].response)
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        let request = CassetteRequest::from(&request);
+        let Some(response) = self.find(&request) else {
+            tracing::warn!(method = %request.method, url = %request.url, "no recorded response");
+            let response = build_response(StatusCode::NOT_FOUND, None, Bytes::new());
+            return Box::pin(futures::future::ready(Ok(response)));
+        };
+        let body = if response.base64 {
+            base64::decode(&response.body).map(Bytes::from)
+        } else {
+            Ok(Bytes::from(response.body.clone()))
+        };
+        let response = body.map_err(Error::from).map(|body| {
+            build_response(
+                StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK),
+                response.content_type.as_deref(),
+                body,
+            )
+        });
+        Box::pin(futures::future::ready(response))
+    }
+}
+
+impl From<&Request> for CassetteRequest {
+    fn from(request: &Request) -> Self {
+        let mut url = request.url().clone();
+        if url.query().is_some() {
+            let pairs = url
+                .query_pairs()
+                .map(|(key, value)| {
+                    let value = if REDACTED_FIELDS.contains(&key.as_ref()) {
+                        REDACTED.into()
+                    } else {
+                        value
+                    };
+                    (key.into_owned(), value.into_owned())
+                })
+                .collect::<Vec<_>>();
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+        }
+        CassetteRequest {
+            method: request.method().to_string(),
+            url: url.to_string(),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(|body| redact_body(&String::from_utf8_lossy(body))),
+        }
+    }
+}
+
+/// Redacts fields in a JSON body, leaving other bodies as they are.
+fn redact_body(body: &str) -> String {
+    fn redact(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    match value {
+                        _ if REDACTED_FIELDS.contains(&key.as_str()) => *value = REDACTED.into(),
+                        serde_json::Value::Object(fields)
+                            if REDACTED_MAP_FIELDS.contains(&key.as_str()) =>
+                        {
+                            for value in fields.values_mut() {
+                                *value = REDACTED.into();
+                            }
+                        }
+                        _ => redact(value),
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+            _ => {}
+        }
+    }
+
+    match serde_json::from_str(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => body.to_owned(),
+    }
+}
+
+fn build_response(status: StatusCode, content_type: Option<&str>, body: Bytes) -> Response {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = status;
+    if let Some(content_type) = content_type.and_then(|value| value.parse().ok()) {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    Response::from(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplayTransport;
+    use crate::persist::temp_path;
+    use crate::test_util::{mock_attachment_upload, png};
+    use crate::{Client, MemoryTransport, Post, PostId};
+
+    #[tokio::test]
+    async fn test_record_replay() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("cassette.json");
+        let transport = MemoryTransport::new();
+        transport.respond_login(1);
+        transport.respond(
+            "GET",
+            "project/example/posts",
+            200,
+            include_str!("../samples/example.project.posts.json"),
+        );
+
+        let session = Client::new()
+            .with_transport(transport)
+            .with_recording(&path)
+            .login("eggbug@website.invalid", "hunter2")
+            .await?;
+        let recorded = session.as_client().get_posts_page("example", 0).await?;
+
+        let cassette = std::fs::read_to_string(&path)?;
+        assert!(!cassette.contains("eggbug@website.invalid"));
+        assert!(!cassette.contains("eggbug%40website.invalid"));
+        assert!(cassette.contains("clientHash\\\":\\\"[redacted]"));
+        assert!(cassette.contains("login/salt?email=%5Bredacted%5D"));
+
+        let client = Client::new().with_transport(ReplayTransport::open(&path).await?);
+        let replayed = client.get_posts_page("example", 0).await?;
+        assert_eq!(replayed.len(), recorded.len());
+        assert_eq!(replayed[0].headline, recorded[0].headline);
+        assert!(client.get_posts_page("example", 1).await.is_err());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_record_attachment_upload() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("cassette-upload.json");
+        let transport = MemoryTransport::new();
+        transport.respond_login(1);
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":1}"#);
+        transport.respond("PUT", "project/eggbug/posts/1", 200, r#"{"postId":1}"#);
+        mock_attachment_upload(&transport, "eggbug", 1);
+
+        let session = Client::new()
+            .with_transport(transport)
+            .with_recording(&path)
+            .login("eggbug@website.invalid", "hunter2")
+            .await?;
+        let mut post = Post {
+            attachments: vec![png()],
+            ..Post::default()
+        };
+        session.create_post("eggbug", &mut post).await?;
+
+        let cassette = std::fs::read_to_string(&path)?;
+        assert!(!cassette.contains("\\\"key\\\":\\\"value\\\""));
+        assert!(cassette.contains("requiredFields\\\":{\\\"key\\\":\\\"[redacted]"));
+        assert!(!cassette.to_lowercase().contains("cookie"));
+
+        // The redacted upload still replays.
+        let session = Client::new()
+            .with_transport(ReplayTransport::open(&path).await?)
+            .login("eggbug@website.invalid", "hunter2")
+            .await?;
+        let mut post = Post {
+            attachments: vec![png()],
+            ..Post::default()
+        };
+        assert_eq!(session.create_post("eggbug", &mut post).await?, PostId(1));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
-use crate::{Error, Post, Session};
-use reqwest::{Method, RequestBuilder};
+use crate::transport::{RequestBuilder, ReqwestTransport, Transport};
+use crate::{Error, Post, SearchResults, Session, TagCursor, TagFeedOptions};
+use futures::Stream;
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::sync::Arc;
 
 const PBKDF2_ITERATIONS: u32 = 200_000;
 const PBKDF2_KEY_LENGTH: usize = 128;
@@ -12,7 +15,10 @@ macro_rules! request_impl {
             #[inline]
             pub(crate) fn $f(&self, path: &str) -> RequestBuilder {
                 tracing::info!(path, concat!("Client::", stringify!($f)));
-                self.client.$f(format!("{}{}", self.base_url, path))
+                RequestBuilder::new(
+                    self.transport.clone(),
+                    self.client.$f(format!("{}{}", self.base_url, path)),
+                )
             }
         )*
     };
@@ -23,7 +29,8 @@ macro_rules! request_impl {
 pub struct Client {
     pub(crate) base_url: Cow<'static, str>,
     pub(crate) client: reqwest::Client,
-    logged_in: bool,
+    transport: Arc<dyn Transport>,
+    pub(crate) logged_in: bool,
 }
 
 impl Client {
@@ -38,13 +45,15 @@ impl Client {
             " (https://github.com/iliana/eggbug-rs)",
         );
 
+        let client = reqwest::Client::builder()
+            .cookie_store(true)
+            .user_agent(USER_AGENT)
+            .build()
+            .unwrap();
         Client {
             base_url: Cow::Borrowed("https://cohost.org/api/v1/"),
-            client: reqwest::Client::builder()
-                .cookie_store(true)
-                .user_agent(USER_AGENT)
-                .build()
-                .unwrap(),
+            transport: Arc::new(ReqwestTransport::new(client.clone())),
+            client,
             logged_in: false,
         }
     }
@@ -59,6 +68,30 @@ impl Client {
         self
     }
 
+    /// Sets the transport used to send requests, in a builder-style function.
+    ///
+    /// Use a [`MemoryTransport`][`crate::MemoryTransport`] to test code without network access.
+    #[must_use]
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Client {
+        self.transport = Arc::new(transport);
+        self
+    }
+
+    /// Records every request and response to a cassette file at `path`, in a builder-style
+    /// function. See [`RecordTransport`][`crate::RecordTransport`] for what is recorded.
+    ///
+    /// Requests are still sent with the client's current transport, so set any other transport
+    /// first.
+    #[cfg(feature = "fs")]
+    #[must_use]
+    pub fn with_recording(mut self, path: impl AsRef<std::path::Path>) -> Client {
+        self.transport = Arc::new(crate::cassette::RecordTransport::from_arc(
+            self.transport,
+            path,
+        ));
+        self
+    }
+
     /// Logs into cohost with an email and password, returning a [`Session`].
     ///
     /// Securely storing the user's password is an exercise left to the caller.
@@ -122,11 +155,64 @@ impl Client {
         Ok(posts_page.into())
     }
 
+    /// Search for projects, tags, and posts matching `query`.
+    ///
+    /// Pages start at 0. Once you get an empty page, there are no more pages after that to get;
+    /// they will all be empty.
+    #[tracing::instrument(skip(self))]
+    pub async fn search(&self, query: &str, page: u64) -> Result<SearchResults, Error> {
+        let results: crate::search::de::SearchResults = self
+            .get("search")
+            .query(&[("query", query), ("page", &page.to_string())])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(results.into())
+    }
+
+    /// Get a page of posts with the given tag, starting from `cursor`.
+    ///
+    /// Once you get an empty page, there are no more posts to get.
+    #[tracing::instrument(skip(self))]
+    pub async fn tagged_posts_page(
+        &self,
+        tag: &str,
+        options: TagFeedOptions,
+        cursor: TagCursor,
+    ) -> Result<Vec<Post>, Error> {
+        options.get_page(self, tag, cursor).await
+    }
+
+    /// Returns a stream of posts with the given tag, newest first, starting from `cursor`.
+    ///
+    /// Pages of posts are requested as the stream is polled. To start from the newest post, use
+    /// [`TagCursor::now`]. To resume reading later, store the cursor you started from and the
+    /// number of posts you read, and pass `cursor.skip(count)` as the new cursor.
+    pub fn tagged_posts<'a>(
+        &'a self,
+        tag: &'a str,
+        options: TagFeedOptions,
+        cursor: TagCursor,
+    ) -> impl Stream<Item = Result<Post, Error>> + 'a {
+        options.stream(self, tag, cursor)
+    }
+
     #[inline]
     pub(crate) fn request(&self, method: Method, path: &str) -> RequestBuilder {
         tracing::info!(%method, path, "Client::request");
-        self.client
-            .request(method, format!("{}{}", self.base_url, path))
+        RequestBuilder::new(
+            self.transport.clone(),
+            self.client
+                .request(method, format!("{}{}", self.base_url, path)),
+        )
+    }
+
+    /// Builds a request to a URL outside the API, such as an attachment upload or download.
+    pub(crate) fn request_url(&self, method: Method, url: &str) -> RequestBuilder {
+        tracing::info!(%method, url, "Client::request_url");
+        RequestBuilder::new(self.transport.clone(), self.client.request(method, url))
     }
 
     request_impl!(delete, get, post, put);
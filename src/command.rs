@@ -0,0 +1,516 @@
+//! Parsing commands out of asks and comments, for bots that respond to them.
+//!
+//! [`Commands`] holds a set of named commands, each with a handler taking typed arguments and
+//! returning the [`Post`] to reply with. Text is turned into plain text first, removing markdown
+//! and HTML, and the command is read from its first line: a word starting with the prefix (`/` by
+//! default), optionally after some `@mentions`, followed by its arguments. Arguments are separated
+//! by whitespace, and can be wrapped in double quotes to include spaces.
+//!
+//! ```
+//! use eggbug::command::{Commands, Rest};
+//! use eggbug::Post;
+//!
+//! let commands = Commands::new()
+//!     .command("roll", |_, (sides,): (u32,)| Post {
+//!         markdown: format!("you rolled a d{}", sides),
+//!         ..Default::default()
+//!     })
+//!     .command("echo", |invocation, (Rest(text),)| Post {
+//!         markdown: match invocation.from {
+//!             Some(handle) => format!("@{} said: {}", handle, text),
+//!             None => format!("someone said: {}", text),
+//!         },
+//!         ..Default::default()
+//!     });
+//!
+//! let reply = commands.run("**/roll** 20", None).unwrap();
+//! assert_eq!(reply.markdown, "you rolled a d20");
+//! assert!(commands.run("just saying hi", None).is_none());
+//! ```
+
+use crate::{Ask, Post, PostId};
+use std::fmt::{self, Write};
+
+/// The prefix commands start with, unless changed with [`Commands::with_prefix`].
+const DEFAULT_PREFIX: &str = "/";
+
+type Handler = Box<dyn Fn(&Invocation<'_>, &[String]) -> Result<Post, CommandError> + Send + Sync>;
+
+/// A set of commands, and how to recognize them.
+pub struct Commands {
+    prefix: String,
+    commands: Vec<Command>,
+}
+
+struct Command {
+    name: String,
+    args: Vec<String>,
+    handler: Handler,
+}
+
+impl Command {
+    fn usage(&self, prefix: &str) -> String {
+        let mut usage = format!("{}{}", prefix, self.name);
+        for arg in &self.args {
+            write!(usage, " {}", arg).unwrap();
+        }
+        usage
+    }
+}
+
+/// Details about how a command was invoked, passed to its handler.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct Invocation<'a> {
+    /// The name of the command, as registered.
+    pub name: &'a str,
+    /// The handle of the project that sent the command, or `None` if it's unknown, such as for
+    /// anonymous asks.
+    pub from: Option<&'a str>,
+}
+
+/// A reason a command couldn't be run.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+#[non_exhaustive]
+pub enum CommandError {
+    /// No command with this name is registered.
+    #[error("unknown command `{0}`")]
+    Unknown(String),
+
+    /// An argument wasn't given.
+    #[error("missing argument {usage}")]
+    MissingArgument {
+        /// How the argument is shown in usage messages.
+        usage: String,
+    },
+
+    /// An argument couldn't be parsed.
+    #[error("`{value}` isn't a valid {usage}")]
+    InvalidArgument {
+        /// How the argument is shown in usage messages.
+        usage: String,
+        /// The argument as given.
+        value: String,
+    },
+
+    /// More arguments were given than the command takes.
+    #[error("too many arguments")]
+    TooManyArguments,
+}
+
+/// A type that can be parsed from a command's arguments.
+///
+/// This is implemented for strings, numbers, `bool`, `char`, and [`PostId`], which each take one
+/// argument; for [`Option`], which takes an argument if there are any left; and for [`Rest`], which
+/// takes all the arguments left.
+pub trait Arg: Sized {
+    /// Returns how the argument is shown in usage messages, such as `<number>`.
+    fn usage() -> String;
+
+    /// Parses the argument from the start of `args`, advancing past what was used.
+    fn take(args: &mut &[String]) -> Result<Self, CommandError>;
+}
+
+/// A tuple of [`Arg`]s that a command handler takes.
+pub trait Args: Sized {
+    /// Returns how the arguments are shown in usage messages.
+    fn usage() -> Vec<String>;
+
+    /// Parses every argument from `args`.
+    fn parse(args: &[String]) -> Result<Self, CommandError>;
+}
+
+/// An [`Arg`] that takes every argument left, joined by spaces.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Rest(pub String);
+
+impl Commands {
+    /// Creates an empty set of commands, with the default `/` prefix.
+    #[must_use]
+    pub fn new() -> Commands {
+        Commands {
+            prefix: DEFAULT_PREFIX.into(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Sets the prefix commands start with, in a builder-style function.
+    #[must_use]
+    pub fn with_prefix(self, prefix: impl Into<String>) -> Commands {
+        Commands {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    /// Registers a command, in a builder-style function.
+    ///
+    /// Command names are matched case-insensitively. `handler` is called with the arguments
+    /// parsed as `A`, a tuple of [`Arg`]s, and returns the post to reply with.
+    #[must_use]
+    pub fn command<A, F>(mut self, name: impl Into<String>, handler: F) -> Commands
+    where
+        A: Args,
+        F: Fn(&Invocation<'_>, A) -> Post + Send + Sync + 'static,
+    {
+        self.commands.push(Command {
+            name: name.into(),
+            args: A::usage(),
+            handler: Box::new(move |invocation, args| Ok(handler(invocation, A::parse(args)?))),
+        });
+        self
+    }
+
+    /// Returns a usage line for each command, such as `/roll <number>`.
+    #[must_use]
+    pub fn usage(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .map(|command| command.usage(&self.prefix))
+            .collect()
+    }
+
+    /// Runs the command in `text`, returning the post to reply with.
+    ///
+    /// Returns `None` if `text` doesn't contain a command. If it contains a command that isn't
+    /// registered or has the wrong arguments, the reply explains what went wrong. `from` is the
+    /// handle of the project that sent the text, if known.
+    #[must_use]
+    pub fn run(&self, text: &str, from: Option<&str>) -> Option<Post> {
+        let (command, result) = self.invoke(text, from)?;
+        Some(result.unwrap_or_else(|err| {
+            let usage = match command {
+                Some(command) => vec![command.usage(&self.prefix)],
+                None => self.usage(),
+            };
+            let mut markdown = format!("{}\n\nusage:", err);
+            for line in usage {
+                write!(markdown, "\n`{}`", line).unwrap();
+            }
+            Post {
+                markdown,
+                ..Post::default()
+            }
+        }))
+    }
+
+    /// Runs the command in `text` like [`Commands::run`], but returns errors instead of replying
+    /// to them.
+    #[must_use]
+    pub fn try_run(&self, text: &str, from: Option<&str>) -> Option<Result<Post, CommandError>> {
+        self.invoke(text, from).map(|(_, result)| result)
+    }
+
+    /// Runs the command in an ask, returning a post to answer it with [`Session::answer_ask`].
+    ///
+    /// The command is sent by the asker's handle, or by no one if the ask was anonymous.
+    ///
+    /// [`Session::answer_ask`]: crate::Session::answer_ask
+    #[must_use]
+    pub fn answer(&self, ask: &Ask) -> Option<Post> {
+        let from = ask.asker.as_ref().map(|asker| asker.handle.as_str());
+        self.run(&ask.content, from)
+    }
+
+    fn invoke(
+        &self,
+        text: &str,
+        from: Option<&str>,
+    ) -> Option<(Option<&Command>, Result<Post, CommandError>)> {
+        let (name, args) = parse(&plain_text(text), &self.prefix)?;
+        let Some(command) = self.find(&name) else {
+            return Some((None, Err(CommandError::Unknown(name))));
+        };
+        let invocation = Invocation {
+            name: &command.name,
+            from,
+        };
+        Some((Some(command), (command.handler)(&invocation, &args)))
+    }
+
+    fn find(&self, name: &str) -> Option<&Command> {
+        self.commands
+            .iter()
+            .find(|command| command.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for Commands {
+    fn default() -> Commands {
+        Commands::new()
+    }
+}
+
+impl fmt::Debug for Commands {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Commands")
+            .field("prefix", &self.prefix)
+            .field("commands", &self.usage())
+            .finish()
+    }
+}
+
+/// Converts markdown, including any HTML in it, to plain text.
+#[must_use]
+pub fn plain_text(markdown: &str) -> String {
+    use pulldown_cmark::{Event, Parser, TagEnd};
+
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(s) | Event::Code(s) => text.push_str(&s),
+            Event::Html(html) => text.push_str(&strip_tags(&html)),
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::BlockQuote(_)
+                | TagEnd::CodeBlock
+                | TagEnd::Item,
+            ) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.trim().to_owned()
+}
+
+/// Removes HTML tags from a block of HTML, keeping the text between them.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Reads a command name and its arguments from the first line of plain text.
+fn parse(text: &str, prefix: &str) -> Option<(String, Vec<String>)> {
+    let line = text.lines().find(|line| !line.trim().is_empty())?;
+    let mut words = split_words(line)
+        .into_iter()
+        .skip_while(|word| word.starts_with('@'));
+    let name = words.next()?.strip_prefix(prefix)?.to_owned();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, words.collect()))
+}
+
+/// Splits a line into words separated by whitespace, keeping double-quoted words together.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut in_word = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+macro_rules! impl_arg_from_str {
+    ($($ty:ty => $usage:literal),* $(,)?) => {
+        $(
+            impl Arg for $ty {
+                fn usage() -> String {
+                    $usage.into()
+                }
+
+                fn take(args: &mut &[String]) -> Result<Self, CommandError> {
+                    let (value, rest) = args
+                        .split_first()
+                        .ok_or_else(|| CommandError::MissingArgument { usage: Self::usage() })?;
+                    *args = rest;
+                    value.parse().map_err(|_| CommandError::InvalidArgument {
+                        usage: Self::usage(),
+                        value: value.clone(),
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_arg_from_str!(
+    String => "<text>",
+    bool => "<true/false>",
+    char => "<character>",
+    i32 => "<number>",
+    i64 => "<number>",
+    u8 => "<number>",
+    u16 => "<number>",
+    u32 => "<number>",
+    u64 => "<number>",
+    usize => "<number>",
+    f64 => "<number>",
+    PostId => "<post id>",
+);
+
+impl<T: Arg> Arg for Option<T> {
+    fn usage() -> String {
+        let usage = T::usage();
+        format!("[{}]", usage.trim_start_matches('<').trim_end_matches('>'))
+    }
+
+    fn take(args: &mut &[String]) -> Result<Self, CommandError> {
+        if args.is_empty() {
+            Ok(None)
+        } else {
+            T::take(args).map(Some)
+        }
+    }
+}
+
+impl Arg for Rest {
+    fn usage() -> String {
+        "<text...>".into()
+    }
+
+    fn take(args: &mut &[String]) -> Result<Self, CommandError> {
+        if args.is_empty() {
+            return Err(CommandError::MissingArgument {
+                usage: Self::usage(),
+            });
+        }
+        let rest = args.join(" ");
+        *args = &[];
+        Ok(Rest(rest))
+    }
+}
+
+macro_rules! impl_args_for_tuple {
+    ($($name:ident),*) => {
+        impl<$($name: Arg),*> Args for ($($name,)*) {
+            fn usage() -> Vec<String> {
+                vec![$($name::usage()),*]
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn parse(mut args: &[String]) -> Result<Self, CommandError> {
+                let parsed = ($($name::take(&mut args)?,)*);
+                if args.is_empty() {
+                    Ok(parsed)
+                } else {
+                    Err(CommandError::TooManyArguments)
+                }
+            }
+        }
+    };
+}
+
+impl_args_for_tuple!();
+impl_args_for_tuple!(A);
+impl_args_for_tuple!(A, B);
+impl_args_for_tuple!(A, B, C);
+impl_args_for_tuple!(A, B, C, D);
+
+#[cfg(test)]
+mod tests {
+    use super::{plain_text, CommandError, Commands, Rest};
+    use crate::{Ask, AskId, Asker, Post};
+
+    fn commands() -> Commands {
+        Commands::new()
+            .command("roll", |_, (count, sides): (u32, Option<u32>)| Post {
+                markdown: format!("{}d{}", count, sides.unwrap_or(6)),
+                ..Post::default()
+            })
+            .command("echo", |invocation, (Rest(text),)| Post {
+                markdown: format!("{}: {}", invocation.from.unwrap_or("anon"), text),
+                ..Post::default()
+            })
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            plain_text("**/roll** _2_ `20`\n\n<div><b>not</b> this</div>"),
+            "/roll 2 20\nnot this"
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let commands = commands();
+        assert_eq!(
+            commands.usage(),
+            ["/roll <number> [number]", "/echo <text...>"]
+        );
+
+        assert_eq!(commands.run("/roll 2 20", None).unwrap().markdown, "2d20");
+        assert_eq!(commands.run("@bot /ROLL 3", None).unwrap().markdown, "3d6");
+        assert_eq!(
+            commands
+                .run("/echo \"hello  there\" friend", Some("eggbug"))
+                .unwrap()
+                .markdown,
+            "eggbug: hello  there friend"
+        );
+        assert!(commands.run("roll 2", None).is_none());
+        assert!(commands.run("", None).is_none());
+
+        assert_eq!(
+            commands.try_run("/roll two", None).unwrap().unwrap_err(),
+            CommandError::InvalidArgument {
+                usage: "<number>".into(),
+                value: "two".into()
+            }
+        );
+        assert_eq!(
+            commands.try_run("/roll 1 2 3", None).unwrap().unwrap_err(),
+            CommandError::TooManyArguments
+        );
+        assert_eq!(
+            commands.run("/roll", None).unwrap().markdown,
+            "missing argument <number>\n\nusage:\n`/roll <number> [number]`"
+        );
+        assert!(commands
+            .run("/dance", None)
+            .unwrap()
+            .markdown
+            .starts_with("unknown command `dance`"));
+    }
+
+    #[test]
+    fn test_answer() {
+        let commands = commands().with_prefix("!");
+        let mut ask = Ask {
+            ask_id: AskId("1".into()),
+            asker: None,
+            content: "!echo hi".into(),
+            sent_at: chrono::Utc::now(),
+        };
+        assert_eq!(commands.answer(&ask).unwrap().markdown, "anon: hi");
+
+        ask.asker = Some(Asker {
+            handle: "eggbug".into(),
+            display_name: "eggbug".into(),
+        });
+        assert_eq!(commands.answer(&ask).unwrap().markdown, "eggbug: hi");
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{Error, Post, Session};
+use serde::Serialize;
+
+/// Options for reading the dashboard with [`Session::dashboard`], matching the filters available
+/// on the site.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct DashboardOptions {
+    /// Hide posts that are shares of other posts.
+    pub hide_shares: bool,
+    /// Hide posts that are replies to other posts in the share tree.
+    pub hide_replies: bool,
+    /// Hide posts that are responses to asks.
+    pub hide_asks: bool,
+    /// Hide posts marked as adult content.
+    pub hide_adult_content: bool,
+}
+
+impl DashboardOptions {
+    pub(crate) async fn get_page(self, session: &Session, page: u64) -> Result<Vec<Post>, Error> {
+        let posts_page: crate::post::PostPage = session
+            .client
+            .get("dashboard")
+            .query(&DashboardQuery {
+                page,
+                hide_shares: self.hide_shares,
+                hide_replies: self.hide_replies,
+                hide_asks: self.hide_asks,
+                hide_adult_content: self.hide_adult_content,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(posts_page.into())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_excessive_bools)]
+struct DashboardQuery {
+    page: u64,
+    hide_shares: bool,
+    hide_replies: bool,
+    hide_asks: bool,
+    hide_adult_content: bool,
+}
@@ -11,15 +11,174 @@ pub enum Error {
     #[error("attempted to use post with failed attachment")]
     FailedAttachment,
 
+    /// Attempted to create or edit a post that breaks one or more of cohost's limits. See
+    /// [`Post::validate`][`crate::Post::validate`].
+    #[error("invalid post: {}", join(.0))]
+    Validation(Vec<ValidationError>),
+
+    /// An attachment's file changed length between creating the attachment and uploading it.
+    #[cfg(feature = "fs")]
+    #[error("attachment file {} changed from {expected} to {actual} bytes", .path.display())]
+    AttachmentChanged {
+        /// The file's path.
+        path: std::path::PathBuf,
+        /// The file's length when the attachment was created.
+        expected: u64,
+        /// The file's length when the attachment was uploaded.
+        actual: u64,
+    },
+
+    /// Attempted to store a post with an ask or an attachment that can't be stored.
+    #[error("post has an ask or an attachment that can't be stored")]
+    Unstorable,
+
     /// An error while decoding a Base64 string.
     #[error("base64 decode error: {0}")]
     Base64Decode(#[from] base64::DecodeError),
 
+    /// An error while parsing an RSS or Atom feed.
+    #[cfg(feature = "bridge")]
+    #[error("feed parse error: {0}")]
+    Feed(#[from] feed_rs::parser::ParseFeedError),
+
     /// An I/O error.
     #[error("i/o error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// A JSON encoding or decoding error.
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
     /// An HTTP client error (including status codes indicating failure).
     #[error("request error: {0}")]
     Request(#[from] reqwest::Error),
 }
+
+impl Error {
+    /// Returns true if sending the same post again would fail the same way: the post itself was
+    /// rejected, either before sending or by cohost with a client error. Errors that renewing the
+    /// session or waiting could fix, such as 401 Unauthorized or 429 Too Many Requests, aren't
+    /// permanent.
+    #[cfg(feature = "fs")]
+    pub(crate) fn is_permanent(&self) -> bool {
+        match self {
+            Error::EmptyPost
+            | Error::FailedAttachment
+            | Error::Validation(_)
+            | Error::AttachmentChanged { .. } => true,
+            Error::Request(err) => err.status().is_some_and(|status| {
+                status.is_client_error()
+                    && !matches!(
+                        status,
+                        reqwest::StatusCode::UNAUTHORIZED
+                            | reqwest::StatusCode::FORBIDDEN
+                            | reqwest::StatusCode::REQUEST_TIMEOUT
+                            | reqwest::StatusCode::TOO_MANY_REQUESTS
+                    )
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// A way in which a post breaks one of cohost's limits, as returned by
+/// [`Post::validate`][`crate::Post::validate`].
+///
+/// Lengths are counted in characters; `index` is the position of the offending tag, content
+/// warning, or attachment in its list.
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// The headline is too long.
+    #[error("headline is {length} characters long (the maximum is {max})")]
+    HeadlineTooLong {
+        /// The headline's length.
+        length: usize,
+        /// The maximum length.
+        max: usize,
+    },
+
+    /// The markdown content is too long.
+    #[error("markdown is {length} characters long (the maximum is {max})")]
+    MarkdownTooLong {
+        /// The markdown's length.
+        length: usize,
+        /// The maximum length.
+        max: usize,
+    },
+
+    /// The post has too many tags.
+    #[error("post has {count} tags (the maximum is {max})")]
+    TooManyTags {
+        /// The number of tags.
+        count: usize,
+        /// The maximum number of tags.
+        max: usize,
+    },
+
+    /// A tag is empty.
+    #[error("tag {index} is empty")]
+    EmptyTag {
+        /// The tag's position.
+        index: usize,
+    },
+
+    /// A tag is too long.
+    #[error("tag {index} is {length} characters long (the maximum is {max})")]
+    TagTooLong {
+        /// The tag's position.
+        index: usize,
+        /// The tag's length.
+        length: usize,
+        /// The maximum length.
+        max: usize,
+    },
+
+    /// The post has too many content warnings.
+    #[error("post has {count} content warnings (the maximum is {max})")]
+    TooManyContentWarnings {
+        /// The number of content warnings.
+        count: usize,
+        /// The maximum number of content warnings.
+        max: usize,
+    },
+
+    /// A content warning is too long.
+    #[error("content warning {index} is {length} characters long (the maximum is {max})")]
+    ContentWarningTooLong {
+        /// The content warning's position.
+        index: usize,
+        /// The content warning's length.
+        length: usize,
+        /// The maximum length.
+        max: usize,
+    },
+
+    /// An attachment is too large to upload.
+    #[error("attachment {index} is {size} bytes (the maximum is {max})")]
+    AttachmentTooLarge {
+        /// The attachment's position.
+        index: usize,
+        /// The attachment's size in bytes.
+        size: u64,
+        /// The maximum size in bytes.
+        max: u64,
+    },
+
+    /// An attachment has a content type cohost doesn't accept.
+    #[error("attachment {index} has unsupported content type {content_type:?}")]
+    UnsupportedAttachmentType {
+        /// The attachment's position.
+        index: usize,
+        /// The attachment's content type.
+        content_type: String,
+    },
+}
+
+fn join(errors: &[ValidationError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
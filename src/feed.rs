@@ -0,0 +1,270 @@
+//! Generating Atom and RSS feeds from posts.
+//!
+//! ```no_run
+//! use eggbug::{feed::FeedInfo, Client};
+//!
+//! # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+//! let posts = Client::new().get_posts_page("eggbug", 0).await?;
+//! let info = FeedInfo {
+//!     title: "eggbug".into(),
+//!     link: "https://cohost.org/eggbug".into(),
+//!     description: "posts from eggbug".into(),
+//! };
+//! std::fs::write("eggbug.xml", eggbug::feed::atom(&info, &posts))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::attachment::guess_content_type;
+use crate::render::{escape, markdown_to_html};
+use crate::{Post, PostMetadata};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+/// The category added to posts marked as adult content, so feed readers can filter them.
+const ADULT_CATEGORY: &str = "adult content";
+
+/// Describes the feed as a whole.
+#[derive(Clone, Debug, Default)]
+pub struct FeedInfo {
+    /// Feed title, usually the project's display name.
+    pub title: String,
+    /// URL of the page the feed is for, usually the project's page.
+    pub link: String,
+    /// Feed description, usually the project's dek.
+    pub description: String,
+}
+
+/// Renders posts as an Atom feed.
+///
+/// Posts without [`metadata`][`Post::metadata`] (that is, posts that weren't read from cohost)
+/// are left out, since they have no URL or publication date. Posts marked as adult content are
+/// given an `adult content` category.
+#[must_use]
+pub fn atom(info: &FeedInfo, posts: &[Post]) -> String {
+    let entries = entries(posts);
+    let updated = entries
+        .iter()
+        .map(|(_, metadata)| metadata.publication_date)
+        .max()
+        .unwrap_or_else(|| DateTime::<Utc>::from(std::time::UNIX_EPOCH));
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    element(&mut xml, 1, "title", &info.title);
+    element(&mut xml, 1, "subtitle", &info.description);
+    element(&mut xml, 1, "id", &info.link);
+    writeln!(xml, "  <link href=\"{}\"/>", escape(&info.link)).unwrap();
+    element(&mut xml, 1, "updated", &updated.to_rfc3339());
+    for (post, metadata) in entries {
+        xml.push_str("  <entry>\n");
+        element(&mut xml, 2, "title", &title(post, metadata));
+        element(&mut xml, 2, "id", &metadata.locations.url);
+        writeln!(
+            xml,
+            "    <link href=\"{}\"/>",
+            escape(&metadata.locations.url)
+        )
+        .unwrap();
+        element(
+            &mut xml,
+            2,
+            "published",
+            &metadata.publication_date.to_rfc3339(),
+        );
+        element(
+            &mut xml,
+            2,
+            "updated",
+            &metadata.publication_date.to_rfc3339(),
+        );
+        xml.push_str("    <author>\n");
+        element(&mut xml, 3, "name", &metadata.posting_project_id);
+        xml.push_str("    </author>\n");
+        for category in categories(post) {
+            writeln!(xml, "    <category term=\"{}\"/>", escape(category)).unwrap();
+        }
+        for url in post.attachments.iter().filter_map(crate::Attachment::url) {
+            writeln!(
+                xml,
+                "    <link rel=\"enclosure\" href=\"{}\" type=\"{}\"/>",
+                escape(url),
+                escape(&enclosure_type(url)),
+            )
+            .unwrap();
+        }
+        writeln!(
+            xml,
+            "    <content type=\"html\">{}</content>",
+            escape(&content_html(post))
+        )
+        .unwrap();
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Renders posts as an RSS 2.0 feed.
+///
+/// Posts without [`metadata`][`Post::metadata`] (that is, posts that weren't read from cohost)
+/// are left out, since they have no URL or publication date. Posts marked as adult content are
+/// given an `adult content` category.
+///
+/// Attachments are only included in each item's description. RSS enclosures must give the file's
+/// length, which cohost doesn't say.
+#[must_use]
+pub fn rss(info: &FeedInfo, posts: &[Post]) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\">\n");
+    xml.push_str("  <channel>\n");
+    element(&mut xml, 2, "title", &info.title);
+    element(&mut xml, 2, "link", &info.link);
+    element(&mut xml, 2, "description", &info.description);
+    for (post, metadata) in entries(posts) {
+        xml.push_str("    <item>\n");
+        element(&mut xml, 3, "title", &title(post, metadata));
+        element(&mut xml, 3, "link", &metadata.locations.url);
+        writeln!(
+            xml,
+            "      <guid isPermaLink=\"true\">{}</guid>",
+            escape(&metadata.locations.url)
+        )
+        .unwrap();
+        element(
+            &mut xml,
+            3,
+            "pubDate",
+            &metadata.publication_date.to_rfc2822(),
+        );
+        for category in categories(post) {
+            element(&mut xml, 3, "category", category);
+        }
+        element(&mut xml, 3, "description", &content_html(post));
+        xml.push_str("    </item>\n");
+    }
+    xml.push_str("  </channel>\n");
+    xml.push_str("</rss>\n");
+    xml
+}
+
+fn entries(posts: &[Post]) -> Vec<(&Post, &PostMetadata)> {
+    posts
+        .iter()
+        .filter_map(|post| Some((post, post.metadata.as_ref()?)))
+        .collect()
+}
+
+fn title(post: &Post, metadata: &PostMetadata) -> String {
+    if post.headline.is_empty() {
+        format!("Post by @{}", metadata.posting_project_id)
+    } else {
+        post.headline.clone()
+    }
+}
+
+fn categories(post: &Post) -> impl Iterator<Item = &str> {
+    post.tags
+        .iter()
+        .map(String::as_str)
+        .chain(post.adult_content.then_some(ADULT_CATEGORY))
+}
+
+fn enclosure_type(url: &str) -> String {
+    guess_content_type(url.split(['?', '#']).next().unwrap_or(url))
+}
+
+/// Renders a post's content warnings, attachments, and markdown as HTML.
+fn content_html(post: &Post) -> String {
+    let mut html = String::new();
+    if !post.content_warnings.is_empty() {
+        writeln!(
+            html,
+            "<p><strong>CW: {}</strong></p>",
+            escape(&post.content_warnings.join(", "))
+        )
+        .unwrap();
+    }
+    for attachment in &post.attachments {
+        if let Some(url) = attachment.url() {
+            writeln!(
+                html,
+                "<p><img src=\"{}\" alt=\"{}\"></p>",
+                escape(url),
+                escape(attachment.alt_text.as_deref().unwrap_or_default())
+            )
+            .unwrap();
+        }
+    }
+    html.push_str(&markdown_to_html(&post.markdown));
+    html
+}
+
+fn element(xml: &mut String, depth: usize, name: &str, text: &str) {
+    writeln!(
+        xml,
+        "{:indent$}<{name}>{}</{name}>",
+        "",
+        escape(text),
+        indent = depth * 2,
+        name = name
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{atom, rss, FeedInfo};
+    use crate::Post;
+
+    fn sample() -> Result<Vec<Post>, serde_json::Error> {
+        let post_page: crate::post::PostPage =
+            serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+        Ok(post_page.into())
+    }
+
+    fn info() -> FeedInfo {
+        FeedInfo {
+            title: "Example Page".into(),
+            link: "https://cohost.org/example".into(),
+            description: "for use in documentation".into(),
+        }
+    }
+
+    #[test]
+    fn test_atom() -> Result<(), Box<dyn std::error::Error>> {
+        let mut posts = sample()?;
+        posts.push(Post {
+            headline: "not from cohost".into(),
+            ..Post::default()
+        });
+        let xml = atom(&info(), &posts);
+        assert!(xml.starts_with("<?xml"));
+        assert_eq!(xml.matches("<entry>").count(), 3);
+        assert!(!xml.contains("not from cohost"));
+        assert!(xml.contains("<title>This is a test post.</title>"));
+        assert!(xml.contains("<updated>2022-11-04T03:29:25.010+00:00</updated>"));
+        assert!(xml.contains(
+            "<category term=\"a very long tag with some symbols &amp;^^$^(*(&amp;^*&amp; in it\"/>"
+        ));
+        assert!(xml.contains("rel=\"enclosure\" href=\"https://staging.cohostcdn.org/attachment/2b1e7477-ba13-4f7e-9547-f0e2668b92b6/cooltext422710535227689.png\" type=\"image/png\""));
+        assert!(xml.contains("&lt;img src="));
+        assert_eq!(xml.matches("<category term=\"adult content\"/>").count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rss() -> Result<(), Box<dyn std::error::Error>> {
+        let xml = rss(&info(), &sample()?);
+        assert_eq!(xml.matches("<item>").count(), 3);
+        assert!(xml.contains("<pubDate>Fri, 4 Nov 2022 03:17:49 +0000</pubDate>"));
+        assert!(xml.contains("<category>test tag one</category>"));
+        assert_eq!(xml.matches("<category>adult content</category>").count(), 1);
+        // Attachment sizes are unknown, so there are no enclosures, only images.
+        assert!(!xml.contains("<enclosure"));
+        assert!(xml.contains("&lt;img src="));
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{Error, Post, PostId, Session};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+
+/// How far back before an attempt to create a post to look for a matching published post, to
+/// account for clock differences between us and cohost.
+const LOOKUP_SLACK_SECONDS: i64 = 60;
+
+/// The maximum number of pages of recent posts to look through for a matching post.
+const LOOKUP_MAX_PAGES: u64 = 3;
+
+/// How long [`create_post`] waits before its first retry. Each further retry waits twice as long.
+const RETRY_BACKOFF_SECONDS: i64 = 1;
+
+/// A hash of a post's headline, markdown, and tags, used to recognize a post that was created by
+/// an earlier attempt whose response never arrived.
+///
+/// Keys are displayed and serialized as hexadecimal strings, so they can be stored alongside
+/// whatever is tracking the post.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct IdempotencyKey([u8; 32]);
+
+impl IdempotencyKey {
+    /// Computes the key for a post's contents.
+    #[must_use]
+    pub fn for_post(post: &Post) -> IdempotencyKey {
+        let mut hasher = Sha256::new();
+        // Length-prefix each field so that content can't shift between fields.
+        for field in [&post.headline, &post.markdown]
+            .into_iter()
+            .chain(&post.tags)
+        {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        IdempotencyKey(hasher.finalize().into())
+    }
+
+    /// Returns true if `post` has the contents this key was computed from.
+    #[must_use]
+    pub fn matches(&self, post: &Post) -> bool {
+        *self == IdempotencyKey::for_post(post)
+    }
+}
+
+impl Display for IdempotencyKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for IdempotencyKey {
+    type Err = InvalidIdempotencyKey;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(InvalidIdempotencyKey);
+        }
+        let mut key = [0; 32];
+        for (byte, pair) in key.iter_mut().zip(s.as_bytes().chunks(2)) {
+            // `pair` is two ASCII bytes, so this is always valid UTF-8.
+            let pair = std::str::from_utf8(pair).map_err(|_| InvalidIdempotencyKey)?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| InvalidIdempotencyKey)?;
+        }
+        Ok(IdempotencyKey(key))
+    }
+}
+
+impl From<IdempotencyKey> for String {
+    fn from(key: IdempotencyKey) -> Self {
+        let mut s = String::with_capacity(64);
+        write!(s, "{}", key).unwrap();
+        s
+    }
+}
+
+impl TryFrom<String> for IdempotencyKey {
+    type Error = InvalidIdempotencyKey;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The error returned when parsing an [`IdempotencyKey`] that isn't 64 hexadecimal digits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, thiserror::Error)]
+#[error("idempotency key must be 64 hexadecimal digits")]
+pub struct InvalidIdempotencyKey;
+
+/// A post matching an [`IdempotencyKey`], found by [`search`].
+enum Match {
+    /// The post, as it was meant to be created.
+    Finished(PostId),
+    /// A post left unfinished by an earlier attempt, such as a draft whose attachments weren't
+    /// all uploaded.
+    Unfinished(PostId),
+}
+
+/// Looks through the project's recent posts for one matching `key` that was published no earlier
+/// than shortly before `since`.
+///
+/// Posts with new attachments are created as drafts first and only finished once the attachments
+/// are uploaded, so unfinished posts are skipped. If the post being looked for is known, a match
+/// must have as many attachments as it does, and be a draft only if it is; otherwise, drafts are
+/// skipped.
+pub(crate) async fn find_post(
+    session: &Session,
+    project: &str,
+    key: IdempotencyKey,
+    since: DateTime<Utc>,
+    post: Option<&Post>,
+) -> Result<Option<PostId>, Error> {
+    Ok(match search(session, project, key, since, post).await? {
+        Some(Match::Finished(id)) => Some(id),
+        Some(Match::Unfinished(_)) | None => None,
+    })
+}
+
+/// Like [`find_post`], but returns an unfinished post if no finished one is found.
+async fn search(
+    session: &Session,
+    project: &str,
+    key: IdempotencyKey,
+    since: DateTime<Utc>,
+    post: Option<&Post>,
+) -> Result<Option<Match>, Error> {
+    let since = since - Duration::seconds(LOOKUP_SLACK_SECONDS);
+    let mut unfinished = None;
+    for page in 0..LOOKUP_MAX_PAGES {
+        let posts = session.client.get_posts_page(project, page).await?;
+        let mut any_recent = false;
+        for candidate in &posts {
+            let metadata = match &candidate.metadata {
+                Some(metadata) => metadata,
+                None => continue,
+            };
+            if metadata.publication_date < since {
+                continue;
+            }
+            any_recent = true;
+            if !key.matches(candidate) {
+                continue;
+            }
+            let finished = match post {
+                Some(post) => {
+                    candidate.draft == post.draft
+                        && candidate.attachments.len() == post.attachments.len()
+                }
+                None => !candidate.draft,
+            };
+            if finished {
+                return Ok(Some(Match::Finished(metadata.locations.id)));
+            }
+            tracing::warn!(
+                id = %metadata.locations.id,
+                "skipping unfinished post from an earlier attempt"
+            );
+            unfinished.get_or_insert(Match::Unfinished(metadata.locations.id));
+        }
+        // Pinned posts can appear first regardless of age, so only stop once an entire page is
+        // older than we're looking for.
+        if !any_recent {
+            break;
+        }
+    }
+    Ok(unfinished)
+}
+
+/// Creates a post, retrying up to `max_attempts` times if the request fails in a way that might
+/// have happened after cohost created the post. Retries wait a second, then twice as long each
+/// time.
+///
+/// Before each retry, the project's recent posts are checked for a post with the same
+/// [`IdempotencyKey`]. If an earlier attempt left the post unfinished, such as a draft whose
+/// attachments were uploaded but which was never published, that post is finished instead of
+/// creating another.
+pub(crate) async fn create_post(
+    session: &Session,
+    project: &str,
+    post: &mut Post,
+    max_attempts: u32,
+) -> Result<PostId, Error> {
+    let key = IdempotencyKey::for_post(post);
+    let started_at = Utc::now();
+    let mut attempt = 1;
+    loop {
+        let mut unfinished = None;
+        if attempt > 1 {
+            let wait = retry_backoff(Duration::seconds(RETRY_BACKOFF_SECONDS), attempt - 1);
+            tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+            match search(session, project, key, started_at, Some(post)).await? {
+                Some(Match::Finished(id)) => {
+                    tracing::info!(%id, %key, "found post created by an earlier attempt");
+                    return Ok(id);
+                }
+                Some(Match::Unfinished(id)) => unfinished = Some(id),
+                None => {}
+            }
+        }
+        let result = match unfinished {
+            Some(id) => {
+                tracing::info!(%id, %key, "finishing post left by an earlier attempt");
+                session.edit_post(project, id, post).await
+            }
+            None => session.create_post(project, post).await,
+        };
+        match result {
+            Ok(id) => return Ok(id),
+            Err(err) if attempt < max_attempts && is_retryable(&err, post) => {
+                tracing::warn!(%err, attempt, "retrying post creation");
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Returns how long to wait before retrying after `attempts` failed attempts: `base` after the
+/// first, doubling after each one after that.
+pub(crate) fn retry_backoff(base: Duration, attempts: u32) -> Duration {
+    base * (1 << attempts.saturating_sub(1).min(16))
+}
+
+/// Returns true if the error could have come from a request that cohost acted on anyway, and the
+/// post can still be sent again. Posts with failed attachments can't be.
+fn is_retryable(err: &Error, post: &Post) -> bool {
+    let retryable = match err {
+        Error::Request(err) => {
+            err.is_timeout()
+                || err.is_connect()
+                || err.is_body()
+                || err.status().is_some_and(|status| status.is_server_error())
+        }
+        _ => false,
+    };
+    retryable && !post.attachments.iter().any(crate::Attachment::is_failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_backoff, IdempotencyKey};
+    use crate::test_util::{mock_attachment_upload, png, posts_page, session, ATTACHMENT_ID};
+    use crate::{MemoryTransport, Post, PostId};
+    use chrono::Duration;
+
+    #[test]
+    fn test_idempotency_key() {
+        let post = Post {
+            headline: "hello".into(),
+            markdown: "world".into(),
+            tags: vec!["a".into(), "b".into()],
+            ..Post::default()
+        };
+        let key = IdempotencyKey::for_post(&post);
+        assert!(key.matches(&Post {
+            headline: "hello".into(),
+            markdown: "world".into(),
+            tags: vec!["a".into(), "b".into()],
+            draft: true,
+            ..Post::default()
+        }));
+        assert!(!key.matches(&Post {
+            headline: "hello".into(),
+            markdown: "world".into(),
+            tags: vec!["ab".into()],
+            ..Post::default()
+        }));
+        assert!(!key.matches(&Post {
+            headline: "hellowor".into(),
+            markdown: "ld".into(),
+            tags: vec!["a".into(), "b".into()],
+            ..Post::default()
+        }));
+    }
+
+    #[test]
+    fn test_idempotency_key_string() -> Result<(), Box<dyn std::error::Error>> {
+        let key = IdempotencyKey::for_post(&Post {
+            headline: "hello".into(),
+            ..Post::default()
+        });
+        let s = key.to_string();
+        assert_eq!(s.len(), 64);
+        assert_eq!(s.parse::<IdempotencyKey>()?, key);
+        assert_eq!(
+            serde_json::from_value::<IdempotencyKey>(serde_json::to_value(key)?)?,
+            key
+        );
+        assert!("not a key".parse::<IdempotencyKey>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        assert_eq!(retry_backoff(Duration::minutes(1), 1), Duration::minutes(1));
+        assert_eq!(retry_backoff(Duration::minutes(1), 2), Duration::minutes(2));
+        assert_eq!(retry_backoff(Duration::minutes(1), 4), Duration::minutes(8));
+    }
+
+    #[tokio::test]
+    async fn test_create_post_finishes_draft() -> Result<(), Box<dyn std::error::Error>> {
+        let transport = MemoryTransport::new();
+        // The first attempt creates a draft and uploads its attachment, but publishing it fails.
+        transport.respond_once("POST", "project/eggbug/posts", 200, r#"{"postId":1}"#);
+        mock_attachment_upload(&transport, "eggbug", 1);
+        transport.respond_once("PUT", "project/eggbug/posts/1", 502, "");
+        transport.respond("PUT", "project/eggbug/posts/1", 200, r#"{"postId":1}"#);
+        // The draft shows up on the project with the same headline, but no attachments.
+        let draft = Post {
+            headline: "hello".into(),
+            draft: true,
+            ..Post::default()
+        };
+        transport.respond_json("GET", "project/eggbug/posts", 200, &posts_page(1, &draft));
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":2}"#);
+        let session = session(&transport);
+
+        let mut post = Post {
+            headline: "hello".into(),
+            attachments: vec![png()],
+            ..Post::default()
+        };
+        assert_eq!(
+            session
+                .create_post_idempotent("eggbug", &mut post, 2)
+                .await?,
+            PostId(1)
+        );
+        let requests = transport.requests();
+        assert_eq!(
+            requests
+                .iter()
+                .filter(|request| request.method == "POST"
+                    && request.url.ends_with("project/eggbug/posts"))
+                .count(),
+            1
+        );
+        let last = requests.last().unwrap();
+        assert_eq!(last.method, "PUT");
+        let last: serde_json::Value = last.json()?;
+        assert_eq!(last["postState"], 1);
+        assert_eq!(
+            last["blocks"][0]["attachment"]["attachmentId"],
+            ATTACHMENT_ID
+        );
+        Ok(())
+    }
+}
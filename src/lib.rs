@@ -34,22 +34,69 @@
 #![deny(elided_lifetimes_in_paths)]
 #![warn(clippy::pedantic, missing_docs)]
 #![allow(
+    clippy::doc_link_with_quotes,
     clippy::manual_let_else,
     clippy::missing_errors_doc,
     clippy::module_name_repetitions,
+    clippy::struct_field_names,
     clippy::uninlined_format_args
 )]
 
+#[cfg(feature = "fs")]
+pub mod archive;
 mod ask;
 mod attachment;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "bot")]
+pub mod bot;
+#[cfg(feature = "bridge")]
+pub mod bridge;
+mod bulk;
+#[cfg(feature = "fs")]
+mod cassette;
 mod client;
+#[cfg(feature = "command")]
+pub mod command;
+mod dashboard;
 mod error;
+#[cfg(feature = "feed")]
+pub mod feed;
+mod idempotency;
+mod notification;
+mod page;
+#[cfg(feature = "fs")]
+mod persist;
 mod post;
+mod project;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "fs")]
+mod scheduler;
+mod search;
 mod session;
+mod tag;
+#[cfg(feature = "test-server")]
+pub mod test_server;
+#[cfg(test)]
+mod test_util;
+mod transport;
 
 pub use crate::ask::{Ask, AskId, Asker};
-pub use crate::attachment::{Attachment, AttachmentId, MediaMetadata};
+pub use crate::attachment::{guess_content_type, Attachment, AttachmentId, MediaMetadata};
+pub use crate::bulk::{BulkOptions, BulkReport, PostFilter};
+#[cfg(feature = "fs")]
+pub use crate::cassette::{RecordTransport, ReplayTransport};
 pub use crate::client::Client;
-pub use crate::error::Error;
+pub use crate::dashboard::DashboardOptions;
+pub use crate::error::{Error, ValidationError};
+pub use crate::idempotency::{IdempotencyKey, InvalidIdempotencyKey};
+pub use crate::notification::{CommentId, Notification, NotificationCursor};
 pub use crate::post::{Post, PostId, PostLocations, PostMetadata};
+pub use crate::project::{Project, ProjectId, ProjectPrivacy, ProjectUpdate};
+#[cfg(feature = "fs")]
+pub use crate::scheduler::{ScheduleId, ScheduleStatus, ScheduledPost, Scheduler};
+pub use crate::search::{SearchResults, TagResult};
 pub use crate::session::Session;
+pub use crate::tag::{TagCursor, TagFeedOptions};
+pub use crate::transport::{MemoryTransport, RecordedRequest, ReqwestTransport, Transport};
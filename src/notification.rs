@@ -0,0 +1,541 @@
+use crate::{AskId, Error, PostId, Project, ProjectId, Session};
+use chrono::{DateTime, Utc};
+use derive_more::{Display, From, FromStr, Into};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use uuid::Uuid;
+
+/// The number of notifications requested per page.
+const PAGE_SIZE: u64 = 40;
+
+/// A comment ID.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    Eq,
+    From,
+    FromStr,
+    Hash,
+    Into,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[serde(transparent)]
+pub struct CommentId(pub Uuid);
+
+/// Describes something that happened to one of the logged-in account's projects.
+/// Notifications can't be created client-side, only decoded when reading content from the server.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Notification {
+    /// A project liked one of your posts.
+    Like {
+        /// The time the post was liked.
+        created_at: DateTime<Utc>,
+        /// The project that liked the post.
+        from_project: Project,
+        /// The post that was liked.
+        to_post: PostId,
+    },
+    /// A project shared one of your posts.
+    Share {
+        /// The time the post was shared.
+        created_at: DateTime<Utc>,
+        /// The project that shared the post.
+        from_project: Project,
+        /// The post that was shared.
+        to_post: PostId,
+        /// The new post created by sharing.
+        share_post: PostId,
+        /// True if the share has no additional content.
+        transparent_share: bool,
+    },
+    /// A project commented on one of your posts.
+    Comment {
+        /// The time the comment was made.
+        created_at: DateTime<Utc>,
+        /// The project that made the comment.
+        from_project: Project,
+        /// The post that was commented on.
+        to_post: PostId,
+        /// The handle of the project that made the post that was commented on, or `None` if
+        /// cohost didn't send the post.
+        to_project: Option<String>,
+        /// The new comment.
+        comment_id: CommentId,
+        /// The comment this comment is replying to, if any.
+        in_reply_to: Option<CommentId>,
+        /// The comment's markdown content, or `None` if cohost didn't send it, such as when the
+        /// comment has been deleted.
+        body: Option<String>,
+    },
+    /// A project followed one of your projects.
+    Follow {
+        /// The time the follow happened.
+        created_at: DateTime<Utc>,
+        /// The project that followed you.
+        from_project: Project,
+    },
+    /// A project sent one of your projects an ask.
+    Ask {
+        /// The time the ask was sent.
+        created_at: DateTime<Utc>,
+        /// The project that sent the ask, if it wasn't sent anonymously.
+        from_project: Option<Project>,
+        /// The ID of the ask.
+        ask_id: AskId,
+    },
+}
+
+impl Notification {
+    /// Returns the time at which this notification was created.
+    #[must_use]
+    pub fn created_at(&self) -> DateTime<Utc> {
+        match self {
+            Notification::Like { created_at, .. }
+            | Notification::Share { created_at, .. }
+            | Notification::Comment { created_at, .. }
+            | Notification::Follow { created_at, .. }
+            | Notification::Ask { created_at, .. } => *created_at,
+        }
+    }
+
+    /// Returns the project that caused this notification, if known.
+    #[must_use]
+    pub fn from_project(&self) -> Option<&Project> {
+        match self {
+            Notification::Like { from_project, .. }
+            | Notification::Share { from_project, .. }
+            | Notification::Comment { from_project, .. }
+            | Notification::Follow { from_project, .. } => Some(from_project),
+            Notification::Ask { from_project, .. } => from_project.as_ref(),
+        }
+    }
+
+    /// Returns a string telling this notification apart from others created at the same time.
+    fn key(&self) -> String {
+        match self {
+            Notification::Like {
+                from_project,
+                to_post,
+                ..
+            } => format!("like:{}:{}", to_post, from_project.id),
+            Notification::Share { share_post, .. } => format!("share:{}", share_post),
+            Notification::Comment { comment_id, .. } => format!("comment:{}", comment_id),
+            Notification::Follow { from_project, .. } => format!("follow:{}", from_project.id),
+            Notification::Ask { ask_id, .. } => format!("ask:{}", ask_id),
+        }
+    }
+}
+
+/// A position in the notification feed.
+///
+/// Several notifications can share a timestamp, so a cursor holds both the time of the newest
+/// notification handled and which notifications from that time have been handled. A notification
+/// that arrives later with the same timestamp is still new.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationCursor {
+    created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    seen: BTreeSet<String>,
+}
+
+impl NotificationCursor {
+    /// Returns a cursor before every notification created at or after `created_at`.
+    #[must_use]
+    pub fn new(created_at: DateTime<Utc>) -> NotificationCursor {
+        NotificationCursor {
+            created_at,
+            seen: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the time of the newest notification handled.
+    #[must_use]
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Returns true if `notification` is after this cursor.
+    #[must_use]
+    pub fn is_new(&self, notification: &Notification) -> bool {
+        let created_at = notification.created_at();
+        created_at > self.created_at
+            || (created_at == self.created_at && !self.seen.contains(&notification.key()))
+    }
+
+    /// Advances the cursor past `notification`, if it's new.
+    pub fn advance(&mut self, notification: &Notification) {
+        let created_at = notification.created_at();
+        if created_at > self.created_at {
+            self.created_at = created_at;
+            self.seen.clear();
+        }
+        if created_at == self.created_at {
+            self.seen.insert(notification.key());
+        }
+    }
+}
+
+/// Get a page of notifications, newest first.
+pub(crate) async fn get_page(session: &Session, page: u64) -> Result<Vec<Notification>, Error> {
+    let list: de::NotificationList = session
+        .client
+        .get("notifications/list")
+        .query(&[
+            ("offset", (page * PAGE_SIZE).to_string()),
+            ("limit", PAGE_SIZE.to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(list.into())
+}
+
+/// Get all notifications after `cursor`, oldest first.
+pub(crate) async fn since(
+    session: &Session,
+    cursor: &NotificationCursor,
+) -> Result<Vec<Notification>, Error> {
+    let mut new = Vec::new();
+    let mut page = 0;
+    loop {
+        let notifications = get_page(session, page).await?;
+        let count = notifications.len();
+        let before = new.len();
+        new.extend(
+            notifications
+                .into_iter()
+                .filter(|notification| notification.created_at() >= cursor.created_at),
+        );
+        // Stop once we reach notifications we've already seen, or run out of them.
+        if count == 0 || new.len() - before < count {
+            break;
+        }
+        page += 1;
+    }
+    // Notifications can shift onto the next page while we're reading, so the same one can be read
+    // twice.
+    let mut keys = BTreeSet::new();
+    new.retain(|notification| cursor.is_new(notification) && keys.insert(notification.key()));
+    new.reverse();
+    Ok(new)
+}
+
+#[cfg(feature = "poll")]
+pub(crate) fn poll(
+    session: &Session,
+    since: Option<NotificationCursor>,
+    interval: std::time::Duration,
+) -> impl futures::Stream<Item = Result<Notification, Error>> + '_ {
+    use futures::StreamExt;
+
+    futures::stream::unfold((since, true), move |(since, first)| async move {
+        if !first {
+            tokio::time::sleep(interval).await;
+        }
+        let (batch, since) = match since {
+            Some(mut cursor) => {
+                let batch = self::since(session, &cursor).await;
+                if let Ok(batch) = &batch {
+                    for notification in batch {
+                        cursor.advance(notification);
+                    }
+                }
+                (batch, Some(cursor))
+            }
+            // Nothing has been seen yet, so start from the newest notifications.
+            None => match get_page(session, 0).await {
+                Ok(notifications) => (Ok(Vec::new()), Some(newest(&notifications))),
+                Err(err) => (Err(err), None),
+            },
+        };
+        Some((batch, (since, false)))
+    })
+    .flat_map(|batch| {
+        let items: Vec<_> = match batch {
+            Ok(notifications) => notifications.into_iter().map(Ok).collect(),
+            Err(err) => vec![Err(err)],
+        };
+        futures::stream::iter(items)
+    })
+}
+
+/// Returns a cursor past every notification in `notifications`, a page from the start of the feed.
+#[cfg(feature = "poll")]
+pub(crate) fn newest(notifications: &[Notification]) -> NotificationCursor {
+    let mut cursor = NotificationCursor::new(
+        notifications
+            .first()
+            .map_or_else(Utc::now, Notification::created_at),
+    );
+    for notification in notifications {
+        cursor.advance(notification);
+    }
+    cursor
+}
+
+impl From<de::NotificationList> for Vec<Notification> {
+    fn from(list: de::NotificationList) -> Self {
+        let de::NotificationList {
+            notifications,
+            projects,
+            posts,
+            mut comments,
+        } = list;
+        let project = |id: ProjectId| {
+            let project = projects.get(&id).cloned().map(Project::from);
+            if project.is_none() {
+                tracing::warn!(%id, "notification refers to unknown project");
+            }
+            project
+        };
+
+        notifications
+            .into_iter()
+            .filter_map(|notification| {
+                Some(match notification {
+                    de::Notification::Like {
+                        created_at,
+                        from_project_id,
+                        to_post_id,
+                    } => Notification::Like {
+                        created_at,
+                        from_project: project(from_project_id)?,
+                        to_post: to_post_id,
+                    },
+                    de::Notification::Share {
+                        created_at,
+                        from_project_id,
+                        to_post_id,
+                        share_post_id,
+                        transparent_share,
+                    } => Notification::Share {
+                        created_at,
+                        from_project: project(from_project_id)?,
+                        to_post: to_post_id,
+                        share_post: share_post_id,
+                        transparent_share,
+                    },
+                    de::Notification::Comment {
+                        created_at,
+                        from_project_id,
+                        to_post_id,
+                        comment_id,
+                        in_reply_to,
+                    } => Notification::Comment {
+                        created_at,
+                        from_project: project(from_project_id)?,
+                        to_post: to_post_id,
+                        to_project: posts
+                            .get(&to_post_id)
+                            .and_then(|post| post.posting_project.as_ref())
+                            .map(|project| project.handle.clone()),
+                        comment_id,
+                        in_reply_to,
+                        body: comments
+                            .remove(&comment_id)
+                            .map(|comment| comment.comment.body),
+                    },
+                    de::Notification::Follow {
+                        created_at,
+                        from_project_id,
+                    } => Notification::Follow {
+                        created_at,
+                        from_project: project(from_project_id)?,
+                    },
+                    de::Notification::Ask {
+                        created_at,
+                        from_project_id,
+                        ask_id,
+                    } => Notification::Ask {
+                        created_at,
+                        from_project: from_project_id.and_then(&project),
+                        ask_id,
+                    },
+                    de::Notification::Unknown => return None,
+                })
+            })
+            .collect()
+    }
+}
+
+mod de {
+    use super::CommentId;
+    use crate::project::de::Project;
+    use crate::{AskId, PostId, ProjectId};
+    use chrono::{DateTime, Utc};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NotificationList {
+        pub notifications: Vec<Notification>,
+        pub projects: HashMap<ProjectId, Project>,
+        #[serde(default)]
+        pub posts: HashMap<PostId, PostInfo>,
+        #[serde(default)]
+        pub comments: HashMap<CommentId, CommentInfo>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PostInfo {
+        #[serde(default)]
+        pub posting_project: Option<PostingProject>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    pub struct PostingProject {
+        pub handle: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CommentInfo {
+        pub comment: Comment,
+        //pub poster: Project,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Comment {
+        pub body: String,
+        //pub comment_id: CommentId,
+        //pub post_id: PostId,
+        //pub in_reply_to: Option<CommentId>,
+        //#[serde(rename = "postedAtISO")]
+        //pub posted_at: DateTime<Utc>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(tag = "type", rename_all = "camelCase")]
+    pub enum Notification {
+        #[serde(rename_all = "camelCase")]
+        Like {
+            created_at: DateTime<Utc>,
+            from_project_id: ProjectId,
+            to_post_id: PostId,
+        },
+        #[serde(rename_all = "camelCase")]
+        Share {
+            created_at: DateTime<Utc>,
+            from_project_id: ProjectId,
+            to_post_id: PostId,
+            share_post_id: PostId,
+            #[serde(default)]
+            transparent_share: bool,
+        },
+        #[serde(rename_all = "camelCase")]
+        Comment {
+            created_at: DateTime<Utc>,
+            from_project_id: ProjectId,
+            to_post_id: PostId,
+            comment_id: CommentId,
+            #[serde(default)]
+            in_reply_to: Option<CommentId>,
+        },
+        #[serde(rename_all = "camelCase")]
+        Follow {
+            created_at: DateTime<Utc>,
+            from_project_id: ProjectId,
+        },
+        #[serde(rename_all = "camelCase")]
+        Ask {
+            created_at: DateTime<Utc>,
+            #[serde(default)]
+            from_project_id: Option<ProjectId>,
+            ask_id: AskId,
+        },
+        // Grouped notifications and any other types we don't know about yet.
+        #[serde(other)]
+        Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{de, since, Notification, NotificationCursor};
+    use crate::test_util::session;
+    use crate::{MemoryTransport, PostId};
+
+    #[test]
+    fn test_parse_notifications_list() -> Result<(), Box<dyn std::error::Error>> {
+        let list: de::NotificationList =
+            serde_json::from_str(include_str!("../samples/notifications.list.json"))?;
+        assert_eq!(list.notifications.len(), 6);
+
+        let notifications = Vec::<Notification>::from(list);
+        // The grouped notification is skipped.
+        assert_eq!(notifications.len(), 5);
+        assert!(matches!(
+            &notifications[0],
+            Notification::Ask { from_project: None, ask_id, .. } if ask_id.0 == "871936863978390843"
+        ));
+        match &notifications[1] {
+            Notification::Comment {
+                from_project,
+                to_post,
+                to_project,
+                in_reply_to,
+                body,
+                ..
+            } => {
+                assert_eq!(from_project.handle, "example-adult");
+                assert_eq!(*to_post, PostId(185_838));
+                assert_eq!(to_project.as_deref(), Some("example"));
+                assert!(in_reply_to.is_none());
+                assert_eq!(body.as_deref(), Some("!roll 2d6"));
+            }
+            other => panic!("expected comment, got {:?}", other),
+        }
+        assert!(matches!(
+            &notifications[2],
+            Notification::Share { share_post, transparent_share: false, .. } if share_post.0 == 185_916
+        ));
+        assert!(matches!(&notifications[3], Notification::Like { .. }));
+        assert!(matches!(&notifications[4], Notification::Follow { .. }));
+        assert!(notifications
+            .windows(2)
+            .all(|pair| pair[0].created_at() >= pair[1].created_at()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_since_same_timestamp() -> Result<(), Box<dyn std::error::Error>> {
+        let transport = MemoryTransport::new();
+        transport.respond(
+            "GET",
+            "notifications/list",
+            200,
+            include_str!("../samples/notifications.list.json"),
+        );
+        let session = session(&transport);
+
+        // A cursor at the share's timestamp that hasn't seen it yet still includes it.
+        let share_time = "2022-11-04T03:31:02.117Z".parse()?;
+        let mut cursor = NotificationCursor::new(share_time);
+        let new = since(&session, &cursor).await?;
+        assert_eq!(new.len(), 3);
+        assert!(matches!(&new[0], Notification::Share { .. }));
+
+        // Once it's been handled, only the notifications after it are new.
+        cursor.advance(&new[0]);
+        assert_eq!(cursor.created_at(), share_time);
+        let new = since(&session, &cursor).await?;
+        assert_eq!(new.len(), 2);
+        assert!(matches!(&new[0], Notification::Comment { .. }));
+        assert!(matches!(&new[1], Notification::Ask { .. }));
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use crate::Error;
+use futures::{Future, Stream, TryStreamExt};
+use serde::Deserialize;
+
+/// A page of items, as returned by cohost's paginated endpoints.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Page<T> {
+    pub(crate) n_items: u64,
+    pub(crate) n_pages: u64,
+    pub(crate) items: Vec<T>,
+}
+
+/// Turns a function fetching numbered pages into a stream of items.
+///
+/// Pages are fetched starting from page 0 as the stream is polled. The stream ends after the first
+/// empty page.
+pub(crate) fn paginate<'a, T, F, Fut>(fetch: F) -> impl Stream<Item = Result<T, Error>> + 'a
+where
+    T: 'a,
+    F: FnMut(u64) -> Fut + 'a,
+    Fut: Future<Output = Result<Vec<T>, Error>> + 'a,
+{
+    futures::stream::try_unfold((fetch, Some(0)), |(mut fetch, page)| async move {
+        let page = match page {
+            Some(page) => page,
+            None => return Ok::<_, Error>(None),
+        };
+        let items = fetch(page).await?;
+        let next = if items.is_empty() {
+            None
+        } else {
+            Some(page + 1)
+        };
+        Ok(Some((
+            futures::stream::iter(items.into_iter().map(Ok)),
+            (fetch, next),
+        )))
+    })
+    .try_flatten()
+}
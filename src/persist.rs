@@ -0,0 +1,23 @@
+//! Keeping state in JSON files on disk.
+
+use crate::Error;
+use serde::Serialize;
+use std::path::Path;
+
+/// Writes `value` to `path` as JSON.
+///
+/// The JSON is written to a temporary file next to `path` first, then renamed over it, so the
+/// file is never left partially written.
+pub(crate) async fn save_json(path: &Path, value: &impl Serialize) -> Result<(), Error> {
+    let mut temp_path = path.to_owned().into_os_string();
+    temp_path.push(".tmp");
+    tokio::fs::write(&temp_path, serde_json::to_vec_pretty(value)?).await?;
+    tokio::fs::rename(&temp_path, path).await?;
+    Ok(())
+}
+
+/// Returns a path in the temporary directory for a test to use, unique to this process.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("eggbug-{}-{}", std::process::id(), name))
+}
@@ -1,10 +1,23 @@
-use crate::{Ask, Asker, Attachment, Error, Session};
+use crate::{Ask, AskId, Asker, Attachment, Error, Session, ValidationError};
 pub(crate) use de::PostPage;
 use derive_more::{Display, From, FromStr, Into};
 use reqwest::Method;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
+/// The maximum length of a headline, in characters.
+pub(crate) const MAX_HEADLINE_LENGTH: usize = 140;
+/// The maximum length of a post's markdown, in characters.
+const MAX_MARKDOWN_LENGTH: usize = 100_000;
+/// The maximum number of tags on a post.
+const MAX_TAGS: usize = 100;
+/// The maximum length of a tag, in characters.
+const MAX_TAG_LENGTH: usize = 256;
+/// The maximum number of content warnings on a post.
+const MAX_CONTENT_WARNINGS: usize = 20;
+/// The maximum length of a content warning, in characters.
+const MAX_CONTENT_WARNING_LENGTH: usize = 256;
+
 /// A post ID.
 #[allow(clippy::module_name_repetitions)]
 #[derive(
@@ -32,14 +45,20 @@ pub struct PostId(pub u64);
 /// When you send a post with [`Session::create_post`] or [`Session::edit_post`], the `Post` must
 /// be mutable. This is because the [`attachments`][`Post::attachments`] field will be modified
 /// with the ID and URL of the uploaded attachment.
-#[derive(Debug, Default)]
+///
+/// To send the same post more than once, such as to several projects, send clones of it. New
+/// attachments are read again from their buffer, file, or function each time they're uploaded.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 #[must_use]
 pub struct Post {
     /// Marks the post as [18+ content](https://help.antisoftware.club/support/solutions/articles/62000225024-what-does-adult-content-mean-).
     pub adult_content: bool,
     /// Post headline, which is displayed above attachments and markdown.
     pub headline: String,
-    /// The ask to which this post is responding, if any.
+    /// The ask to which this post is responding, if any. This is ignored when creating or editing
+    /// a post; use [`Session::answer_ask`] to answer an ask.
     pub ask: Option<Ask>,
     /// List of attachments, displayed between the headline and markdown.
     pub attachments: Vec<Attachment>,
@@ -59,7 +78,8 @@ pub struct Post {
 }
 
 /// Metadata returned by the cohost API for posts retrieved from post pages.
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[non_exhaustive]
 #[allow(clippy::struct_excessive_bools, clippy::module_name_repetitions)]
 pub struct PostMetadata {
@@ -94,6 +114,7 @@ pub struct PostMetadata {
 
 /// All identifying information about where to find a post, from its ID to how to edit it.
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 #[non_exhaustive]
 #[allow(clippy::module_name_repetitions)]
 pub struct PostLocations {
@@ -115,6 +136,73 @@ impl Post {
         self.attachments.is_empty() && self.headline.is_empty() && self.markdown.is_empty()
     }
 
+    /// Checks the post against cohost's limits on headline, markdown, tag, and content warning
+    /// lengths, and on the size and type of attachments that haven't been uploaded yet.
+    ///
+    /// Returns every problem found, or an empty list if there are none. Creating or editing a post
+    /// calls this first, returning [`Error::Validation`] before sending anything to cohost.
+    #[must_use]
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        let length = self.headline.chars().count();
+        if length > MAX_HEADLINE_LENGTH {
+            errors.push(ValidationError::HeadlineTooLong {
+                length,
+                max: MAX_HEADLINE_LENGTH,
+            });
+        }
+        let length = self.markdown.chars().count();
+        if length > MAX_MARKDOWN_LENGTH {
+            errors.push(ValidationError::MarkdownTooLong {
+                length,
+                max: MAX_MARKDOWN_LENGTH,
+            });
+        }
+
+        if self.tags.len() > MAX_TAGS {
+            errors.push(ValidationError::TooManyTags {
+                count: self.tags.len(),
+                max: MAX_TAGS,
+            });
+        }
+        for (index, tag) in self.tags.iter().enumerate() {
+            let length = tag.chars().count();
+            if tag.trim().is_empty() {
+                errors.push(ValidationError::EmptyTag { index });
+            } else if length > MAX_TAG_LENGTH {
+                errors.push(ValidationError::TagTooLong {
+                    index,
+                    length,
+                    max: MAX_TAG_LENGTH,
+                });
+            }
+        }
+
+        if self.content_warnings.len() > MAX_CONTENT_WARNINGS {
+            errors.push(ValidationError::TooManyContentWarnings {
+                count: self.content_warnings.len(),
+                max: MAX_CONTENT_WARNINGS,
+            });
+        }
+        for (index, cw) in self.content_warnings.iter().enumerate() {
+            let length = cw.chars().count();
+            if length > MAX_CONTENT_WARNING_LENGTH {
+                errors.push(ValidationError::ContentWarningTooLong {
+                    index,
+                    length,
+                    max: MAX_CONTENT_WARNING_LENGTH,
+                });
+            }
+        }
+
+        for (index, attachment) in self.attachments.iter().enumerate() {
+            errors.extend(attachment.validate(index));
+        }
+
+        errors
+    }
+
     pub(crate) async fn send(
         &mut self,
         session: &Session,
@@ -122,6 +210,7 @@ impl Post {
         path: &str,
         project: &str,
         shared_post: Option<PostId>,
+        answer: Option<&AskId>,
     ) -> Result<PostId, Error> {
         if self.is_empty() && shared_post.is_none() {
             return Err(Error::EmptyPost);
@@ -129,13 +218,17 @@ impl Post {
         if self.attachments.iter().any(Attachment::is_failed) {
             return Err(Error::FailedAttachment);
         }
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(Error::Validation(errors));
+        }
 
         let need_upload = self.attachments.iter().any(Attachment::is_new);
 
         let de::PostResponse { post_id } = session
             .client
             .request(method, path)
-            .json(&self.as_api(need_upload, shared_post))
+            .json(&self.as_api(need_upload, shared_post, answer))
             .send()
             .await?
             .error_for_status()?
@@ -154,7 +247,7 @@ impl Post {
             session
                 .client
                 .put(&format!("project/{}/posts/{}", project, post_id))
-                .json(&self.as_api(false, shared_post))
+                .json(&self.as_api(false, shared_post, None))
                 .send()
                 .await?
                 .error_for_status()?;
@@ -164,7 +257,12 @@ impl Post {
     }
 
     #[tracing::instrument]
-    fn as_api(&self, force_draft: bool, shared_post: Option<PostId>) -> ser::Post<'_> {
+    fn as_api<'a>(
+        &'a self,
+        force_draft: bool,
+        shared_post: Option<PostId>,
+        answer: Option<&'a AskId>,
+    ) -> ser::Post<'a> {
         let mut blocks = self
             .attachments
             .iter()
@@ -192,6 +290,7 @@ impl Post {
             cws: &self.content_warnings,
             headline: &self.headline,
             post_state,
+            response_to_ask_id: answer,
             share_of_post_id: shared_post,
             tags: &self.tags,
         };
@@ -226,7 +325,7 @@ impl From<de::Post> for Post {
                     .collect();
                 if related_projects.is_empty() {
                     related_projects.push(api.posting_project.handle.clone());
-                };
+                }
                 related_projects
             },
             posting_project_id: api.posting_project.handle,
@@ -302,6 +401,7 @@ impl From<PostPage> for Vec<Post> {
 mod ser {
     use super::PostId;
     use crate::attachment::AttachmentId;
+    use crate::AskId;
     use serde::Serialize;
     use std::fmt::{self, Debug};
 
@@ -314,6 +414,8 @@ mod ser {
         pub headline: &'a str,
         pub post_state: u64,
         #[serde(skip_serializing_if = "Option::is_none")]
+        pub response_to_ask_id: Option<&'a AskId>,
+        #[serde(skip_serializing_if = "Option::is_none")]
         pub share_of_post_id: Option<PostId>,
         pub tags: &'a [String],
     }
@@ -346,18 +448,13 @@ mod ser {
     }
 }
 
-mod de {
+pub(crate) mod de {
     use super::PostId;
+    use crate::project::de::Project;
     use crate::{AskId, AttachmentId};
     use serde::Deserialize;
 
-    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct PostPage {
-        pub(super) n_items: u64,
-        pub(super) n_pages: u64,
-        pub(super) items: Vec<Post>,
-    }
+    pub type PostPage = crate::page::Page<Post>;
 
     #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -382,9 +479,9 @@ mod de {
         pub plain_text_body: String,
         pub post_edit_url: String,
         pub post_id: PostId,
-        pub posting_project: PostingProject,
+        pub posting_project: Project,
         pub published_at: chrono::DateTime<chrono::Utc>,
-        pub related_projects: Vec<PostingProject>,
+        pub related_projects: Vec<Project>,
         pub share_tree: Vec<Post>,
         pub single_post_page_url: String,
         pub state: u64,
@@ -392,24 +489,6 @@ mod de {
         //pub transparent_share_of_post_id: Option<PostId>,
     }
 
-    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
-    #[serde(rename_all = "camelCase")]
-    pub struct PostingProject {
-        pub handle: String,
-        //pub display_name: Option<String>,
-        //pub dek: Option<String>,
-        //pub description: Option<String>,
-        //#[serde(rename = "avatarURL")]
-        //pub avatar_url: String,
-        //#[serde(rename = "avatarPreviewURL")]
-        //pub avatar_preview_url: String,
-        //pub project_id: ProjectId,
-        //pub privacy: String,
-        //pub pronouns: Option<String>,
-        //pub url: Option<String>,
-        //pub avatar_shape: String,
-    }
-
     #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
     #[serde(rename_all = "camelCase")]
     pub struct AskingProject {
@@ -572,3 +651,159 @@ fn test_convert_project_post_with_ask() -> Result<(), Box<dyn std::error::Error>
     assert_eq!(asker.handle, "asunchaser".to_string());
     Ok(())
 }
+
+#[test]
+fn test_validate() {
+    use crate::MediaMetadata;
+
+    assert_eq!(Post::default().validate(), Vec::new());
+    let post = Post {
+        headline: "h".repeat(MAX_HEADLINE_LENGTH),
+        markdown: "ok".into(),
+        tags: vec!["é".repeat(MAX_TAG_LENGTH)],
+        attachments: vec![Attachment::new(
+            Vec::new(),
+            "egg.png".into(),
+            "image/png".into(),
+            MediaMetadata::Image {
+                width: None,
+                height: None,
+            },
+        )],
+        ..Post::default()
+    };
+    assert_eq!(post.validate(), Vec::new());
+
+    let post = Post {
+        headline: "h".repeat(MAX_HEADLINE_LENGTH + 1),
+        tags: vec!["ok".into(), " ".into(), "t".repeat(MAX_TAG_LENGTH + 1)],
+        content_warnings: vec![String::new(); MAX_CONTENT_WARNINGS + 1],
+        attachments: vec![Attachment::new(
+            Vec::new(),
+            "egg.svg".into(),
+            "image/svg+xml".into(),
+            MediaMetadata::Image {
+                width: None,
+                height: None,
+            },
+        )],
+        ..Post::default()
+    };
+    assert_eq!(
+        post.validate(),
+        vec![
+            ValidationError::HeadlineTooLong {
+                length: MAX_HEADLINE_LENGTH + 1,
+                max: MAX_HEADLINE_LENGTH
+            },
+            ValidationError::EmptyTag { index: 1 },
+            ValidationError::TagTooLong {
+                index: 2,
+                length: MAX_TAG_LENGTH + 1,
+                max: MAX_TAG_LENGTH
+            },
+            ValidationError::TooManyContentWarnings {
+                count: MAX_CONTENT_WARNINGS + 1,
+                max: MAX_CONTENT_WARNINGS
+            },
+            ValidationError::UnsupportedAttachmentType {
+                index: 0,
+                content_type: "image/svg+xml".into()
+            },
+        ]
+    );
+    assert_eq!(
+        Error::Validation(post.validate()[..2].to_vec()).to_string(),
+        "invalid post: headline is 141 characters long (the maximum is 140); tag 1 is empty"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::MediaMetadata;
+
+    for sample in [
+        include_str!("../samples/example.project.posts.json"),
+        include_str!("../samples/with-ask.project.posts.json"),
+    ] {
+        let post_page: de::PostPage = serde_json::from_str(sample)?;
+        for post in post_page.items {
+            let post = Post::from(post);
+            let deserialized: Post = serde_json::from_value(serde_json::to_value(&post)?)?;
+            assert_eq!(deserialized, post);
+        }
+    }
+
+    let post_page: de::PostPage =
+        serde_json::from_str(include_str!("../samples/example.project.posts.json"))?;
+    let post = post_page
+        .items
+        .into_iter()
+        .map(Post::from)
+        .find(|post| !post.attachments.is_empty())
+        .expect("no post with attachments in sample");
+    let serialized = serde_json::to_value(&post)?;
+    let attachment = &serialized["attachments"][0];
+    assert_eq!(
+        attachment["id"],
+        post.attachments[0].id().unwrap().to_string()
+    );
+    assert_eq!(attachment["url"], post.attachments[0].url().unwrap());
+    assert!(serialized["metadata"]["locations"]["url"].is_string());
+
+    let post = Post {
+        attachments: vec![Attachment::new(
+            Vec::new(),
+            "egg.png".into(),
+            "image/png".into(),
+            MediaMetadata::Image {
+                width: None,
+                height: None,
+            },
+        )],
+        ..Post::default()
+    };
+    let err = serde_json::to_value(&post).unwrap_err();
+    assert!(err.to_string().contains("hasn't been uploaded"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::session;
+    use crate::{Ask, AskId, MemoryTransport, Post, PostId};
+
+    #[tokio::test]
+    async fn test_answer_ask() -> Result<(), Box<dyn std::error::Error>> {
+        let transport = MemoryTransport::new();
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":1}"#);
+        transport.respond("PUT", "project/eggbug/posts/1", 200, r#"{"postId":1}"#);
+        let session = session(&transport);
+
+        let ask = Ask {
+            ask_id: AskId("871936863978390843".into()),
+            asker: None,
+            content: "hello?".into(),
+            sent_at: chrono::Utc::now(),
+        };
+        // A post's own `ask`, such as one read from cohost, is never sent.
+        let mut post = Post {
+            markdown: "hi!".into(),
+            ask: Some(ask.clone()),
+            ..Post::default()
+        };
+        session.create_post("eggbug", &mut post).await?;
+        session.answer_ask("eggbug", &ask, &mut post).await?;
+        session.edit_post("eggbug", PostId(1), &mut post).await?;
+
+        let requests = transport.requests();
+        let created: serde_json::Value = requests[0].json()?;
+        assert!(created.get("responseToAskId").is_none());
+        let answered: serde_json::Value = requests[1].json()?;
+        assert_eq!(answered["responseToAskId"], "871936863978390843");
+        let edited: serde_json::Value = requests[2].json()?;
+        assert!(edited.get("responseToAskId").is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{Attachment, Error, Session};
+use derive_more::{Display, From, FromStr, Into};
+use serde::{Deserialize, Serialize};
+
+/// A project ID.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    Eq,
+    From,
+    FromStr,
+    Hash,
+    Into,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[serde(transparent)]
+pub struct ProjectId(pub u64);
+
+/// Describes a project, the pages that posts are made from and that follow each other.
+/// Projects can't be created client-side, only decoded when reading content from the server.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Project {
+    /// The unique numerical ID of the project.
+    pub id: ProjectId,
+    /// The unique handle of the project.
+    pub handle: String,
+    /// The display name of the project, which may be different from the handle.
+    pub display_name: String,
+    /// Short description, displayed under the display name.
+    pub dek: String,
+    /// Long description (markdown), displayed on the project's profile page.
+    pub description: String,
+    /// Pronouns.
+    pub pronouns: String,
+    /// Website URL.
+    pub url: String,
+    /// URL of the project's avatar image.
+    pub avatar_url: String,
+    /// URL of the project's header image, if it has one.
+    pub header_url: Option<String>,
+    /// Visibility of the project's posts.
+    pub privacy: ProjectPrivacy,
+}
+
+/// The visibility of a project's posts.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProjectPrivacy {
+    /// Posts are visible to everyone.
+    Public,
+    /// Posts are only visible to approved followers.
+    Private,
+}
+
+/// Describes changes to a project's profile.
+///
+/// Fields set to `None` are left unchanged. When you send an update with
+/// [`Session::update_project`], the `ProjectUpdate` must be mutable, because the
+/// [`avatar`][`ProjectUpdate::avatar`] and [`header`][`ProjectUpdate::header`] attachments will be
+/// modified with the ID and URL of the uploaded image.
+#[derive(Debug, Default)]
+#[must_use]
+pub struct ProjectUpdate {
+    /// Display name, shown in place of the handle.
+    pub display_name: Option<String>,
+    /// Short description, displayed under the display name.
+    pub dek: Option<String>,
+    /// Long description (markdown), displayed on the project's profile page.
+    pub description: Option<String>,
+    /// Pronouns.
+    pub pronouns: Option<String>,
+    /// Website URL.
+    pub url: Option<String>,
+    /// Visibility of the project's posts.
+    pub privacy: Option<ProjectPrivacy>,
+    /// New avatar image.
+    pub avatar: Option<Attachment>,
+    /// New header image.
+    pub header: Option<Attachment>,
+}
+
+impl ProjectUpdate {
+    pub(crate) async fn send(&mut self, session: &Session, project: &str) -> Result<(), Error> {
+        for (kind, attachment) in [("avatar", &mut self.avatar), ("header", &mut self.header)] {
+            if let Some(attachment) = attachment {
+                attachment
+                    .upload_with(
+                        &session.client,
+                        "trpc/projects.attachment.start",
+                        ser::AttachmentTarget {
+                            project_handle: project,
+                            kind,
+                        },
+                        |attachment_id| {
+                            format!("project/{}/attach/finish/{}", project, attachment_id)
+                        },
+                    )
+                    .await?;
+            }
+        }
+
+        session
+            .client
+            .put(&format!("project/{}", project))
+            .json(&self.as_api())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    fn as_api(&self) -> ser::ProjectUpdate<'_> {
+        let update = ser::ProjectUpdate {
+            display_name: self.display_name.as_deref(),
+            dek: self.dek.as_deref(),
+            description: self.description.as_deref(),
+            pronouns: self.pronouns.as_deref(),
+            url: self.url.as_deref(),
+            privacy: self.privacy,
+            avatar_attachment_id: self.avatar.as_ref().and_then(Attachment::id),
+            header_attachment_id: self.header.as_ref().and_then(Attachment::id),
+        };
+        tracing::debug!(?update);
+        update
+    }
+}
+
+impl From<de::Project> for Project {
+    fn from(api: de::Project) -> Self {
+        Self {
+            id: api.project_id,
+            display_name: api
+                .display_name
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| api.handle.clone()),
+            handle: api.handle,
+            dek: api.dek.unwrap_or_default(),
+            description: api.description.unwrap_or_default(),
+            pronouns: api.pronouns.unwrap_or_default(),
+            url: api.url.unwrap_or_default(),
+            avatar_url: api.avatar_url,
+            header_url: api.header_url,
+            privacy: api.privacy,
+        }
+    }
+}
+
+mod ser {
+    use super::ProjectPrivacy;
+    use crate::AttachmentId;
+    use serde::Serialize;
+
+    #[derive(Debug, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ProjectUpdate<'a> {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub display_name: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub dek: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub description: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub pronouns: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub url: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub privacy: Option<ProjectPrivacy>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub avatar_attachment_id: Option<AttachmentId>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub header_attachment_id: Option<AttachmentId>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct AttachmentTarget<'a> {
+        pub project_handle: &'a str,
+        pub kind: &'a str,
+    }
+}
+
+pub(crate) mod de {
+    use super::{ProjectId, ProjectPrivacy};
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Project {
+        pub handle: String,
+        pub display_name: Option<String>,
+        pub dek: Option<String>,
+        pub description: Option<String>,
+        #[serde(rename = "avatarURL")]
+        pub avatar_url: String,
+        //#[serde(rename = "avatarPreviewURL")]
+        //pub avatar_preview_url: String,
+        #[serde(default, rename = "headerURL")]
+        pub header_url: Option<String>,
+        pub project_id: ProjectId,
+        pub privacy: ProjectPrivacy,
+        #[serde(default)]
+        pub pronouns: Option<String>,
+        #[serde(default)]
+        pub url: Option<String>,
+        //pub avatar_shape: String,
+    }
+
+    pub type ProjectPage = crate::page::Page<Project>;
+}
+
+#[test]
+fn test_parse_project_followers_page() -> Result<(), Box<dyn std::error::Error>> {
+    let page: de::ProjectPage =
+        serde_json::from_str(include_str!("../samples/example.project.followers.json"))?;
+    assert_eq!(usize::try_from(page.n_items).unwrap(), page.items.len());
+    assert_eq!(page.n_pages, 1);
+
+    let projects: Vec<Project> = page.items.into_iter().map(Project::from).collect();
+    let project = projects
+        .iter()
+        .find(|project| project.handle == "example-adult")
+        .expect("Couldn't find project example-adult as expected; did you change the sample?");
+    assert_eq!(project.id, ProjectId(49_508));
+    assert_eq!(project.display_name, "example-adult");
+    assert_eq!(project.privacy, ProjectPrivacy::Public);
+    assert!(project.header_url.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_serialize_project_update() -> Result<(), Box<dyn std::error::Error>> {
+    let update = ProjectUpdate {
+        dek: Some("feeling sleepy".into()),
+        privacy: Some(ProjectPrivacy::Private),
+        ..Default::default()
+    };
+    assert_eq!(
+        serde_json::to_value(update.as_api())?,
+        serde_json::json!({ "dek": "feeling sleepy", "privacy": "private" })
+    );
+    Ok(())
+}
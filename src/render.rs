@@ -0,0 +1,480 @@
+//! Rendering posts as HTML, for previewing them before they're published.
+//!
+//! Markdown is rendered the way cohost renders it: GitHub-flavored markdown where single line
+//! breaks are kept, with any HTML in it cleaned down to the tags, attributes, and CSS properties
+//! cohost allows. The rendered HTML doesn't depend on anything but the post, so it can be used
+//! for snapshot tests.
+//!
+//! ```
+//! use eggbug::Post;
+//!
+//! let post = Post {
+//!     headline: "hello".into(),
+//!     markdown: "the part everyone sees\n\n---\n\nthe part behind \"read more\"".into(),
+//!     ..Post::default()
+//! };
+//! let html = eggbug::render::render(&post);
+//! assert!(html.contains("<details class=\"read-more\">"));
+//! ```
+
+use crate::attachment::{guess_content_type, Finished, Inner};
+use crate::{Ask, Attachment, MediaMetadata, Post};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+/// The markdown block that separates the part of a post shown on the dashboard from the part
+/// behind "read more".
+const READ_MORE: &str = "---";
+
+/// CSS properties allowed in `style` attributes.
+const ALLOWED_CSS_PROPERTIES: &[&str] = &[
+    "align-content",
+    "align-items",
+    "align-self",
+    "animation",
+    "animation-delay",
+    "animation-direction",
+    "animation-duration",
+    "animation-fill-mode",
+    "animation-iteration-count",
+    "animation-name",
+    "animation-play-state",
+    "animation-timing-function",
+    "aspect-ratio",
+    "background",
+    "background-attachment",
+    "background-clip",
+    "background-color",
+    "background-image",
+    "background-origin",
+    "background-position",
+    "background-repeat",
+    "background-size",
+    "border",
+    "border-bottom",
+    "border-collapse",
+    "border-color",
+    "border-left",
+    "border-radius",
+    "border-right",
+    "border-style",
+    "border-top",
+    "border-width",
+    "bottom",
+    "box-shadow",
+    "box-sizing",
+    "clip-path",
+    "color",
+    "column-gap",
+    "columns",
+    "cursor",
+    "display",
+    "filter",
+    "flex",
+    "flex-basis",
+    "flex-direction",
+    "flex-grow",
+    "flex-shrink",
+    "flex-wrap",
+    "float",
+    "font",
+    "font-family",
+    "font-size",
+    "font-style",
+    "font-variant",
+    "font-weight",
+    "gap",
+    "grid",
+    "grid-area",
+    "grid-column",
+    "grid-row",
+    "grid-template",
+    "grid-template-areas",
+    "grid-template-columns",
+    "grid-template-rows",
+    "height",
+    "justify-content",
+    "justify-items",
+    "justify-self",
+    "left",
+    "letter-spacing",
+    "line-height",
+    "list-style",
+    "margin",
+    "margin-bottom",
+    "margin-left",
+    "margin-right",
+    "margin-top",
+    "max-height",
+    "max-width",
+    "min-height",
+    "min-width",
+    "mix-blend-mode",
+    "object-fit",
+    "object-position",
+    "opacity",
+    "order",
+    "outline",
+    "overflow",
+    "overflow-x",
+    "overflow-y",
+    "padding",
+    "padding-bottom",
+    "padding-left",
+    "padding-right",
+    "padding-top",
+    "place-items",
+    "pointer-events",
+    "position",
+    "resize",
+    "right",
+    "rotate",
+    "row-gap",
+    "scale",
+    "text-align",
+    "text-decoration",
+    "text-indent",
+    "text-shadow",
+    "text-transform",
+    "top",
+    "transform",
+    "transform-origin",
+    "transition",
+    "translate",
+    "user-select",
+    "vertical-align",
+    "visibility",
+    "white-space",
+    "width",
+    "word-break",
+    "writing-mode",
+    "z-index",
+];
+
+/// Renders a post as an HTML `<article>`: its headline, content warnings, ask, attachments,
+/// markdown, and tags.
+///
+/// Markdown after a `---` block is rendered inside a `<details class="read-more">` element.
+/// Attachments that haven't been uploaded yet are rendered with their file names in place of
+/// URLs.
+#[must_use]
+pub fn render(post: &Post) -> String {
+    let mut html = String::from("<article class=\"post\">\n");
+    if !post.headline.is_empty() {
+        writeln!(
+            html,
+            "<h1 class=\"headline\">{}</h1>",
+            escape(&post.headline)
+        )
+        .unwrap();
+    }
+    if post.adult_content || !post.content_warnings.is_empty() {
+        let mut warnings = Vec::new();
+        if post.adult_content {
+            warnings.push("18+".to_owned());
+        }
+        warnings.extend(post.content_warnings.iter().map(|cw| escape(cw)));
+        writeln!(
+            html,
+            "<p class=\"content-warnings\">CW: {}</p>",
+            warnings.join(", ")
+        )
+        .unwrap();
+    }
+    if let Some(ask) = &post.ask {
+        html.push_str(&render_ask(ask));
+    }
+    for attachment in &post.attachments {
+        html.push_str(&render_attachment(attachment));
+    }
+
+    let (preview, read_more) = split_read_more(&post.markdown);
+    if !preview.is_empty() {
+        writeln!(
+            html,
+            "<div class=\"body\">\n{}</div>",
+            markdown_to_html(preview)
+        )
+        .unwrap();
+    }
+    if let Some(read_more) = read_more {
+        writeln!(
+            html,
+            "<details class=\"read-more\">\n<summary>read more</summary>\n\
+             <div class=\"body\">\n{}</div>\n</details>",
+            markdown_to_html(read_more)
+        )
+        .unwrap();
+    }
+
+    if !post.tags.is_empty() {
+        html.push_str("<ul class=\"tags\">\n");
+        for tag in &post.tags {
+            writeln!(html, "<li>#{}</li>", escape(tag)).unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</article>\n");
+    html
+}
+
+/// Splits markdown at its first `---` block into the part shown on the dashboard and the part
+/// behind "read more", if any.
+///
+/// Only a `---` that is a horizontal rule of its own splits the markdown, not one in a code
+/// block, list, or quote, or one underlining a heading.
+#[must_use]
+pub fn split_read_more(markdown: &str) -> (&str, Option<&str>) {
+    use pulldown_cmark::{Event, Parser};
+
+    let mut depth = 0_usize;
+    for (event, range) in Parser::new_ext(markdown, markdown_options()).into_offset_iter() {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Rule if depth == 0 && markdown[range.clone()].trim() == READ_MORE => {
+                return (
+                    markdown[..range.start].trim_end_matches('\n'),
+                    Some(markdown[range.end..].trim_start_matches('\n')),
+                );
+            }
+            _ => {}
+        }
+    }
+    (markdown, None)
+}
+
+/// Renders markdown as HTML the way cohost does, cleaning any HTML in it down to what cohost
+/// allows.
+#[must_use]
+pub fn markdown_to_html(markdown: &str) -> String {
+    use pulldown_cmark::{Event, Parser};
+
+    let parser = Parser::new_ext(markdown, markdown_options()).map(|event| match event {
+        Event::SoftBreak => Event::HardBreak,
+        event => event,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    sanitizer().clean(&html).to_string()
+}
+
+/// The markdown extensions cohost supports.
+fn markdown_options() -> pulldown_cmark::Options {
+    use pulldown_cmark::Options;
+
+    Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS
+}
+
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+        builder
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["checked", "disabled", "type"])
+            .add_tag_attributes("img", ["height", "width"])
+            .add_generic_attributes(["style"])
+            .filter_style_properties(
+                ALLOWED_CSS_PROPERTIES
+                    .iter()
+                    .copied()
+                    .collect::<HashSet<_>>(),
+            );
+        builder
+    })
+}
+
+fn render_ask(ask: &Ask) -> String {
+    let asker = match &ask.asker {
+        Some(asker) => format!("@{}", escape(&asker.handle)),
+        None => "Anonymous user".to_owned(),
+    };
+    format!(
+        "<blockquote class=\"ask\">\n<p class=\"asker\">{} asked:</p>\n{}</blockquote>\n",
+        asker,
+        markdown_to_html(&ask.content)
+    )
+}
+
+fn render_attachment(attachment: &Attachment) -> String {
+    let (src, content_type, caption) = match &attachment.kind {
+        Inner::New {
+            filename,
+            content_type,
+            metadata,
+            ..
+        } => {
+            let caption = match metadata {
+                Some(MediaMetadata::Audio { artist, title }) => {
+                    format!("{} - {}", artist, title)
+                }
+                _ => filename.clone(),
+            };
+            (filename.as_str(), content_type.clone(), Some(caption))
+        }
+        Inner::Uploaded(Finished { url, .. }) => (
+            url.as_str(),
+            guess_content_type(url.split(['?', '#']).next().unwrap_or(url)),
+            None,
+        ),
+        Inner::Failed => return "<figure class=\"attachment failed\"></figure>\n".to_owned(),
+    };
+
+    let mut html = String::from("<figure class=\"attachment\">\n");
+    if content_type.starts_with("audio/") {
+        writeln!(html, "<audio controls src=\"{}\"></audio>", escape(src)).unwrap();
+    } else {
+        writeln!(
+            html,
+            "<img src=\"{}\" alt=\"{}\">",
+            escape(src),
+            escape(attachment.alt_text.as_deref().unwrap_or_default())
+        )
+        .unwrap();
+    }
+    if let Some(caption) = caption {
+        writeln!(html, "<figcaption>{}</figcaption>", escape(&caption)).unwrap();
+    }
+    html.push_str("</figure>\n");
+    html
+}
+
+/// Escapes text for use in HTML or XML, including inside quoted attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{markdown_to_html, render, split_read_more};
+    use crate::{Attachment, MediaMetadata, Post};
+
+    #[test]
+    fn test_split_read_more() {
+        assert_eq!(split_read_more("one\n\ntwo"), ("one\n\ntwo", None));
+        assert_eq!(
+            split_read_more("one\n\n---\n\ntwo\n\n---\n\nthree"),
+            ("one", Some("two\n\n---\n\nthree"))
+        );
+        assert_eq!(split_read_more("---\n\ntwo"), ("", Some("two")));
+        // A `---` that isn't its own block is a heading underline, not a read-more split.
+        assert_eq!(
+            split_read_more("one\n---\n\ntwo"),
+            ("one\n---\n\ntwo", None)
+        );
+        // Neither is one in a code block or a quote.
+        let fenced = "```\none\n\n---\n\ntwo\n```";
+        assert_eq!(split_read_more(fenced), (fenced, None));
+        assert_eq!(
+            split_read_more("> one\n>\n> ---"),
+            ("> one\n>\n> ---", None)
+        );
+        assert_eq!(
+            split_read_more("```\n---\n```\n\n---\n\nmore"),
+            ("```\n---\n```", Some("more"))
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_html() {
+        assert_eq!(
+            markdown_to_html("one\ntwo ~~three~~"),
+            "<p>one<br>\ntwo <del>three</del></p>\n"
+        );
+        assert_eq!(
+            markdown_to_html(
+                "<div style=\"color: red; position: fixed; -webkit-evil: 1\" onclick=\"x()\">hi</div>"
+            ),
+            "<div style=\"color:red;position:fixed\">hi</div>"
+        );
+        assert_eq!(
+            markdown_to_html(
+                "<script>alert(1)</script><iframe src=\"https://x.invalid\"></iframe>"
+            ),
+            ""
+        );
+        assert_eq!(
+            markdown_to_html("[link](javascript:alert(1))"),
+            "<p><a rel=\"noopener noreferrer\">link</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_render() -> Result<(), Box<dyn std::error::Error>> {
+        let post = Post {
+            adult_content: true,
+            headline: "a <headline>".into(),
+            attachments: vec![
+                Attachment::new(
+                    Vec::new(),
+                    "egg.png".into(),
+                    "image/png".into(),
+                    MediaMetadata::Image {
+                        width: None,
+                        height: None,
+                    },
+                )
+                .with_alt_text("an egg".into()),
+                Attachment::new(
+                    Vec::new(),
+                    "song.mp3".into(),
+                    "audio/mpeg".into(),
+                    MediaMetadata::Audio {
+                        artist: "eggbug".into(),
+                        title: "the egg song".into(),
+                    },
+                ),
+            ],
+            markdown: "before\n\n---\n\nafter".into(),
+            tags: vec!["eggs".into()],
+            content_warnings: vec!["bugs".into()],
+            ..Post::default()
+        };
+        assert_eq!(
+            render(&post),
+            "<article class=\"post\">\n\
+             <h1 class=\"headline\">a &lt;headline&gt;</h1>\n\
+             <p class=\"content-warnings\">CW: 18+, bugs</p>\n\
+             <figure class=\"attachment\">\n\
+             <img src=\"egg.png\" alt=\"an egg\">\n\
+             <figcaption>egg.png</figcaption>\n\
+             </figure>\n\
+             <figure class=\"attachment\">\n\
+             <audio controls src=\"song.mp3\"></audio>\n\
+             <figcaption>eggbug - the egg song</figcaption>\n\
+             </figure>\n\
+             <div class=\"body\">\n<p>before</p>\n</div>\n\
+             <details class=\"read-more\">\n<summary>read more</summary>\n\
+             <div class=\"body\">\n<p>after</p>\n</div>\n</details>\n\
+             <ul class=\"tags\">\n<li>#eggs</li>\n</ul>\n\
+             </article>\n"
+        );
+
+        let post_page: crate::post::PostPage =
+            serde_json::from_str(include_str!("../samples/with-ask.project.posts.json"))?;
+        let posts = Vec::<Post>::from(post_page);
+        let post = posts
+            .iter()
+            .find(|post| post.ask.is_some())
+            .expect("no post with an ask in the sample");
+        let html = render(post);
+        assert!(html.contains("<blockquote class=\"ask\">\n<p class=\"asker\">@"));
+        Ok(())
+    }
+}
@@ -0,0 +1,708 @@
+use crate::attachment::{Finished, Inner, Source};
+use crate::idempotency::retry_backoff;
+use crate::persist::save_json;
+use crate::{
+    Attachment, AttachmentId, Error, IdempotencyKey, MediaMetadata, Post, PostId, Session,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use derive_more::{Display, From, FromStr, Into};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::{Path, PathBuf};
+
+/// The number of attempts made to publish a post before giving up, unless changed with
+/// [`Scheduler::with_max_attempts`].
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// How long to wait before the first retry. Each further retry waits twice as long.
+const RETRY_BACKOFF_SECONDS: i64 = 60;
+
+/// A scheduled post ID, unique within a [`Scheduler`]'s file.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Deserialize,
+    Display,
+    Eq,
+    From,
+    FromStr,
+    Hash,
+    Into,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    Serialize,
+)]
+#[serde(transparent)]
+pub struct ScheduleId(pub u64);
+
+/// A queue of posts to publish at given times, persisted to a JSON file.
+///
+/// Each time [`Scheduler::run_due`] is called, posts that are due are published with
+/// [`Session::create_post`]. Every change is written to the file before and after publishing, so
+/// if the process is restarted, posts that were published are not published again. Before a post
+/// is retried, whether its last attempt failed or was interrupted, it is looked up on the project
+/// by its [`IdempotencyKey`], in case cohost created it anyway.
+///
+/// Posts are stored in the file, along with the contents of attachments made from buffers.
+/// Attachments made from files are read from their files when the post is published. Posts with
+/// asks, or with attachments made with [`Attachment::new_from_fn`], can't be scheduled.
+#[derive(Debug)]
+pub struct Scheduler {
+    path: PathBuf,
+    max_attempts: u32,
+    state: State,
+}
+
+/// A post in a [`Scheduler`]'s queue.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub struct ScheduledPost {
+    /// The ID of this entry in the queue.
+    pub id: ScheduleId,
+    /// The handle of the project to publish to.
+    pub project: String,
+    /// The time at which the post should be published.
+    pub publish_at: DateTime<Utc>,
+    /// The state of this entry.
+    pub status: ScheduleStatus,
+    /// The number of failed attempts to publish this post.
+    pub attempts: u32,
+    /// The error from the last failed attempt, if any.
+    pub last_error: Option<String>,
+    /// The time of the next attempt, if the last one failed.
+    pub retry_at: Option<DateTime<Utc>>,
+    /// When the first attempt to publish the post started; posts created since then are checked
+    /// before each retry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_attempt_at: Option<DateTime<Utc>>,
+    post: StoredPost,
+}
+
+/// The state of a [`ScheduledPost`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ScheduleStatus {
+    /// The post has not been published yet.
+    Pending,
+    /// An attempt to publish the post started at `started_at`, but its result was never recorded.
+    #[serde(rename_all = "camelCase")]
+    Sending {
+        /// When the attempt started.
+        started_at: DateTime<Utc>,
+    },
+    /// The post was published.
+    #[serde(rename_all = "camelCase")]
+    Published {
+        /// The ID of the new post.
+        post_id: PostId,
+    },
+    /// Publishing the post failed too many times, or in a way that retrying can't fix, such as
+    /// the post being rejected as invalid; it won't be retried.
+    Failed,
+}
+
+impl ScheduledPost {
+    /// Returns a copy of the post to be published.
+    pub fn post(&self) -> Post {
+        self.post.to_post()
+    }
+
+    fn due_at(&self) -> Option<DateTime<Utc>> {
+        match self.status {
+            ScheduleStatus::Pending => Some(self.retry_at.unwrap_or(self.publish_at)),
+            ScheduleStatus::Sending { .. } => Some(self.publish_at),
+            ScheduleStatus::Published { .. } | ScheduleStatus::Failed => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct State {
+    next_id: u64,
+    entries: Vec<ScheduledPost>,
+}
+
+/// The stored contents of a post. Asks can't be stored.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredPost {
+    adult_content: bool,
+    headline: String,
+    markdown: String,
+    tags: Vec<String>,
+    content_warnings: Vec<String>,
+    draft: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<StoredAttachment>,
+}
+
+impl StoredPost {
+    fn from_post(post: &Post) -> Result<StoredPost, Error> {
+        if post.ask.is_some() {
+            return Err(Error::Unstorable);
+        }
+        Ok(StoredPost {
+            adult_content: post.adult_content,
+            headline: post.headline.clone(),
+            markdown: post.markdown.clone(),
+            tags: post.tags.clone(),
+            content_warnings: post.content_warnings.clone(),
+            draft: post.draft,
+            attachments: post
+                .attachments
+                .iter()
+                .map(StoredAttachment::from_attachment)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn to_post(&self) -> Post {
+        Post {
+            adult_content: self.adult_content,
+            headline: self.headline.clone(),
+            markdown: self.markdown.clone(),
+            tags: self.tags.clone(),
+            content_warnings: self.content_warnings.clone(),
+            draft: self.draft,
+            attachments: self
+                .attachments
+                .iter()
+                .map(StoredAttachment::to_attachment)
+                .collect(),
+            ..Post::default()
+        }
+    }
+}
+
+/// The stored form of an attachment: uploaded attachments by their ID and URL, and new ones by
+/// where their contents come from. Failed attachments and ones made from functions can't be
+/// stored.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+enum StoredAttachment {
+    #[serde(rename_all = "camelCase")]
+    Uploaded {
+        attachment_id: AttachmentId,
+        url: String,
+        alt_text: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    New {
+        source: StoredSource,
+        filename: String,
+        content_type: String,
+        content_length: u64,
+        metadata: Option<StoredMetadata>,
+        alt_text: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum StoredSource {
+    /// The attachment's contents, encoded as Base64.
+    #[serde(
+        serialize_with = "serialize_base64",
+        deserialize_with = "deserialize_base64"
+    )]
+    Base64(Bytes),
+    /// The path of the file to read the attachment's contents from.
+    File(PathBuf),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum StoredMetadata {
+    Image {
+        width: Option<u32>,
+        height: Option<u32>,
+    },
+    Audio {
+        artist: String,
+        title: String,
+    },
+}
+
+impl StoredAttachment {
+    fn from_attachment(attachment: &Attachment) -> Result<StoredAttachment, Error> {
+        let alt_text = attachment.alt_text.clone();
+        Ok(match &attachment.kind {
+            Inner::Uploaded(Finished { attachment_id, url }) => StoredAttachment::Uploaded {
+                attachment_id: *attachment_id,
+                url: url.clone(),
+                alt_text,
+            },
+            Inner::New {
+                source,
+                filename,
+                content_type,
+                content_length,
+                metadata,
+            } => StoredAttachment::New {
+                source: match source {
+                    Source::Bytes(bytes) => StoredSource::Base64(bytes.clone()),
+                    Source::File(path) => StoredSource::File(path.clone()),
+                    Source::Fn(_) => return Err(Error::Unstorable),
+                },
+                filename: filename.clone(),
+                content_type: content_type.clone(),
+                content_length: *content_length,
+                metadata: metadata.clone().map(|metadata| match metadata {
+                    MediaMetadata::Image { width, height } => {
+                        StoredMetadata::Image { width, height }
+                    }
+                    MediaMetadata::Audio { artist, title } => {
+                        StoredMetadata::Audio { artist, title }
+                    }
+                }),
+                alt_text,
+            },
+            Inner::Failed => return Err(Error::Unstorable),
+        })
+    }
+
+    fn to_attachment(&self) -> Attachment {
+        match self.clone() {
+            StoredAttachment::Uploaded {
+                attachment_id,
+                url,
+                alt_text,
+            } => Attachment {
+                kind: Inner::Uploaded(Finished { attachment_id, url }),
+                alt_text,
+            },
+            StoredAttachment::New {
+                source,
+                filename,
+                content_type,
+                content_length,
+                metadata,
+                alt_text,
+            } => Attachment {
+                kind: Inner::New {
+                    source: match source {
+                        StoredSource::Base64(bytes) => Source::Bytes(bytes),
+                        StoredSource::File(path) => Source::File(path),
+                    },
+                    filename,
+                    content_type,
+                    content_length,
+                    metadata: metadata.map(|metadata| match metadata {
+                        StoredMetadata::Image { width, height } => {
+                            MediaMetadata::Image { width, height }
+                        }
+                        StoredMetadata::Audio { artist, title } => {
+                            MediaMetadata::Audio { artist, title }
+                        }
+                    }),
+                },
+                alt_text,
+            },
+        }
+    }
+}
+
+fn serialize_base64<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64::encode(bytes))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    base64::decode(encoded)
+        .map(Bytes::from)
+        .map_err(serde::de::Error::custom)
+}
+
+impl Scheduler {
+    /// Opens the scheduler file at `path`, creating an empty queue if the file doesn't exist.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Scheduler, Error> {
+        let path = path.as_ref().to_owned();
+        let state = match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Scheduler {
+            path,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            state,
+        })
+    }
+
+    /// Sets the number of attempts made to publish a post before giving up, in a builder-style
+    /// function.
+    #[must_use]
+    pub fn with_max_attempts(self, max_attempts: u32) -> Scheduler {
+        Scheduler {
+            max_attempts,
+            ..self
+        }
+    }
+
+    /// Adds a post to the queue, to be published on `project` at `publish_at`.
+    pub async fn schedule(
+        &mut self,
+        project: &str,
+        post: &Post,
+        publish_at: DateTime<Utc>,
+    ) -> Result<ScheduleId, Error> {
+        let id = ScheduleId(self.state.next_id);
+        self.state.entries.push(ScheduledPost {
+            id,
+            project: project.to_owned(),
+            publish_at,
+            status: ScheduleStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            retry_at: None,
+            first_attempt_at: None,
+            post: StoredPost::from_post(post)?,
+        });
+        self.state.next_id += 1;
+        self.save().await?;
+        Ok(id)
+    }
+
+    /// Removes a post from the queue that has not been published yet.
+    ///
+    /// Returns true if the post was removed.
+    pub async fn cancel(&mut self, id: ScheduleId) -> Result<bool, Error> {
+        let before = self.state.entries.len();
+        self.state
+            .entries
+            .retain(|entry| entry.id != id || entry.status != ScheduleStatus::Pending);
+        if self.state.entries.len() == before {
+            return Ok(false);
+        }
+        self.save().await?;
+        Ok(true)
+    }
+
+    /// Returns all entries in the queue, including published and failed posts.
+    #[must_use]
+    pub fn entries(&self) -> &[ScheduledPost] {
+        &self.state.entries
+    }
+
+    /// Returns the time at which the next post is due, if there are any left to publish.
+    #[must_use]
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.state
+            .entries
+            .iter()
+            .filter_map(ScheduledPost::due_at)
+            .min()
+    }
+
+    /// Publishes all posts that are due.
+    ///
+    /// Returns the IDs and results of the posts that were attempted. Posts that fail are retried
+    /// later with an increasing delay, until they have been attempted too many times; posts that
+    /// are rejected as invalid aren't retried. Errors writing the scheduler file are returned
+    /// immediately.
+    #[tracing::instrument(skip(self, session))]
+    #[allow(clippy::type_complexity)]
+    pub async fn run_due(
+        &mut self,
+        session: &Session,
+    ) -> Result<Vec<(ScheduleId, Result<PostId, Error>)>, Error> {
+        let now = Utc::now();
+        let due = self
+            .state
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.due_at().is_some_and(|due_at| due_at <= now))
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::new();
+        for index in due {
+            let result = self.publish(session, index).await?;
+            results.push((self.state.entries[index].id, result));
+        }
+        Ok(results)
+    }
+
+    /// Publishes posts as they become due, checking the queue at least every `interval`.
+    ///
+    /// This only returns if writing the scheduler file fails.
+    #[cfg(feature = "poll")]
+    pub async fn run(
+        &mut self,
+        session: &Session,
+        interval: std::time::Duration,
+    ) -> Result<(), Error> {
+        loop {
+            for (id, result) in self.run_due(session).await? {
+                if let Err(err) = result {
+                    tracing::warn!(%id, %err, "failed to publish scheduled post");
+                }
+            }
+            let wait = self
+                .next_due()
+                .and_then(|due_at| (due_at - Utc::now()).to_std().ok())
+                .map_or(interval, |wait| wait.min(interval));
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn publish(
+        &mut self,
+        session: &Session,
+        index: usize,
+    ) -> Result<Result<PostId, Error>, Error> {
+        let entry = &self.state.entries[index];
+        tracing::info!(id = %entry.id, project = %entry.project, "publishing scheduled post");
+
+        // A previous attempt failed or was interrupted; it may have created the post anyway.
+        let since = match entry.status {
+            ScheduleStatus::Sending { started_at } => {
+                Some(entry.first_attempt_at.unwrap_or(started_at))
+            }
+            _ => entry.first_attempt_at,
+        };
+        if let Some(started_at) = since {
+            let post = entry.post();
+            let key = IdempotencyKey::for_post(&post);
+            match crate::idempotency::find_post(
+                session,
+                &entry.project,
+                key,
+                started_at,
+                Some(&post),
+            )
+            .await
+            {
+                Ok(Some(post_id)) => {
+                    tracing::info!(%post_id, "found post from earlier attempt");
+                    self.state.entries[index].status = ScheduleStatus::Published { post_id };
+                    self.save().await?;
+                    return Ok(Ok(post_id));
+                }
+                Ok(None) => {}
+                Err(err) => return self.record_failure(index, err).await.map(Err),
+            }
+        }
+
+        let entry = &mut self.state.entries[index];
+        let started_at = Utc::now();
+        entry.status = ScheduleStatus::Sending { started_at };
+        entry.first_attempt_at.get_or_insert(started_at);
+        let project = entry.project.clone();
+        let mut post = entry.post.to_post();
+        self.save().await?;
+
+        match session.create_post(&project, &mut post).await {
+            Ok(post_id) => {
+                let entry = &mut self.state.entries[index];
+                entry.status = ScheduleStatus::Published { post_id };
+                entry.retry_at = None;
+                self.save().await?;
+                Ok(Ok(post_id))
+            }
+            Err(err) => self.record_failure(index, err).await.map(Err),
+        }
+    }
+
+    async fn record_failure(&mut self, index: usize, err: Error) -> Result<Error, Error> {
+        let entry = &mut self.state.entries[index];
+        entry.attempts += 1;
+        entry.last_error = Some(err.to_string());
+        if entry.attempts >= self.max_attempts || err.is_permanent() {
+            entry.status = ScheduleStatus::Failed;
+            entry.retry_at = None;
+        } else {
+            entry.status = ScheduleStatus::Pending;
+            entry.retry_at = Some(
+                Utc::now()
+                    + retry_backoff(Duration::seconds(RETRY_BACKOFF_SECONDS), entry.attempts),
+            );
+        }
+        self.save().await?;
+        Ok(err)
+    }
+
+    async fn save(&self) -> Result<(), Error> {
+        save_json(&self.path, &self.state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScheduleStatus, Scheduler};
+    use crate::persist::temp_path;
+    use crate::test_util::{posts_page, session, PNG};
+    use crate::{Attachment, Error, MediaMetadata, MemoryTransport, Post, PostId};
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[tokio::test]
+    async fn test_schedule_persists() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("schedule-persists.json");
+        let later = Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap();
+
+        let mut scheduler = Scheduler::open(&path).await?;
+        let post = Post {
+            headline: "good morning".into(),
+            tags: vec!["bots".into()],
+            ..Post::default()
+        };
+        let first = scheduler.schedule("eggbug", &post, later).await?;
+        let second = scheduler
+            .schedule("eggbug", &post, later - Duration::hours(1))
+            .await?;
+        assert_ne!(first, second);
+        assert!(scheduler.cancel(first).await?);
+        assert!(!scheduler.cancel(first).await?);
+
+        let scheduler = Scheduler::open(&path).await?;
+        assert_eq!(scheduler.entries().len(), 1);
+        let entry = &scheduler.entries()[0];
+        assert_eq!(entry.id, second);
+        assert_eq!(entry.project, "eggbug");
+        assert_eq!(entry.status, ScheduleStatus::Pending);
+        assert_eq!(entry.post().headline, "good morning");
+        assert_eq!(entry.post().tags, vec!["bots".to_owned()]);
+        assert_eq!(scheduler.next_due(), Some(later - Duration::hours(1)));
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schedule_stores_attachments() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("schedule-attachments.json");
+        let mut scheduler = Scheduler::open(&path).await?;
+        let image = Attachment::new(
+            PNG,
+            "image.png".into(),
+            "image/png".into(),
+            MediaMetadata::Image {
+                width: Some(1),
+                height: Some(1),
+            },
+        )
+        .with_alt_text("a very small image".into());
+        let post = Post {
+            attachments: vec![image],
+            ..Post::default()
+        };
+        scheduler.schedule("eggbug", &post, Utc::now()).await?;
+
+        let scheduler = Scheduler::open(&path).await?;
+        assert_eq!(scheduler.entries()[0].post(), post);
+
+        // Attachments made from functions can't be read back after a restart.
+        let mut scheduler = scheduler;
+        let post = Post {
+            attachments: vec![Attachment::new_from_fn(
+                || futures::stream::iter([Ok::<_, std::io::Error>(PNG)]),
+                "image.png".into(),
+                "image/png".into(),
+                16,
+                None,
+            )],
+            ..Post::default()
+        };
+        assert!(matches!(
+            scheduler.schedule("eggbug", &post, Utc::now()).await,
+            Err(Error::Unstorable)
+        ));
+        assert_eq!(scheduler.entries().len(), 1);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_due_finds_post_after_error() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("schedule-retry.json");
+        let _ = std::fs::remove_file(&path);
+        let transport = MemoryTransport::new();
+        // cohost creates the post, but the response is lost.
+        transport.respond_once("POST", "project/eggbug/posts", 500, "oops");
+        transport.respond("POST", "project/eggbug/posts", 200, r#"{"postId":2}"#);
+        let session = session(&transport);
+
+        let mut scheduler = Scheduler::open(&path).await?;
+        let post = Post {
+            headline: "good morning".into(),
+            ..Post::default()
+        };
+        let id = scheduler.schedule("eggbug", &post, Utc::now()).await?;
+        let results = scheduler.run_due(&session).await?;
+        assert!(results[0].1.is_err());
+        assert_eq!(scheduler.entries()[0].status, ScheduleStatus::Pending);
+
+        transport.respond_json("GET", "project/eggbug/posts", 200, &posts_page(1, &post));
+
+        // Retry now rather than waiting out the backoff.
+        let mut scheduler = Scheduler::open(&path).await?;
+        scheduler.state.entries[0].retry_at = Some(Utc::now());
+        let results = scheduler.run_due(&session).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, id);
+        assert_eq!(*results[0].1.as_ref().unwrap(), PostId(1));
+        assert_eq!(
+            scheduler.entries()[0].status,
+            ScheduleStatus::Published { post_id: PostId(1) }
+        );
+        let posts = transport
+            .requests()
+            .into_iter()
+            .filter(|request| request.method == "POST")
+            .count();
+        assert_eq!(posts, 1);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_due_gives_up_on_rejected_posts() -> Result<(), Box<dyn std::error::Error>> {
+        let path = temp_path("schedule-rejected.json");
+        let transport = MemoryTransport::new();
+        transport.respond("POST", "project/eggbug/posts", 400, "bad request");
+        let session = session(&transport);
+
+        let mut scheduler = Scheduler::open(&path).await?;
+        let post = Post {
+            headline: "good morning".into(),
+            ..Post::default()
+        };
+        let rejected = scheduler.schedule("eggbug", &post, Utc::now()).await?;
+        let invalid = scheduler
+            .schedule(
+                "eggbug",
+                &Post {
+                    tags: vec![String::new()],
+                    ..post
+                },
+                Utc::now(),
+            )
+            .await?;
+
+        let results = scheduler.run_due(&session).await?;
+        assert_eq!(results.len(), 2);
+        for id in [rejected, invalid] {
+            let entry = scheduler
+                .entries()
+                .iter()
+                .find(|entry| entry.id == id)
+                .unwrap();
+            assert_eq!(entry.status, ScheduleStatus::Failed);
+            assert_eq!(entry.attempts, 1);
+        }
+        assert_eq!(scheduler.next_due(), None);
+        // The invalid post is never sent.
+        assert_eq!(transport.requests().len(), 1);
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
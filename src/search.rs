@@ -0,0 +1,98 @@
+use crate::{Post, Project};
+
+/// Results of a search with [`Client::search`][`crate::Client::search`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct SearchResults {
+    /// Projects matching the query.
+    pub projects: Vec<Project>,
+    /// Tags matching the query.
+    pub tags: Vec<TagResult>,
+    /// Posts matching the query.
+    pub posts: Vec<Post>,
+}
+
+impl SearchResults {
+    /// Returns true if there are no results of any kind.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty() && self.tags.is_empty() && self.posts.is_empty()
+    }
+}
+
+/// A tag returned from a search.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct TagResult {
+    /// The tag, as it is written on posts.
+    pub tag: String,
+    /// The number of posts using this tag, if known.
+    pub post_count: Option<u64>,
+}
+
+impl From<de::SearchResults> for SearchResults {
+    fn from(api: de::SearchResults) -> Self {
+        Self {
+            projects: api.projects.into_iter().map(Project::from).collect(),
+            tags: api
+                .tags
+                .into_iter()
+                .map(|tag| TagResult {
+                    tag: tag.content,
+                    post_count: tag.count,
+                })
+                .collect(),
+            posts: api.posts.into_iter().map(Post::from).collect(),
+        }
+    }
+}
+
+pub(crate) mod de {
+    use crate::post::de::Post;
+    use crate::project::de::Project;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SearchResults {
+        #[serde(default)]
+        pub projects: Vec<Project>,
+        #[serde(default)]
+        pub tags: Vec<Tag>,
+        #[serde(default)]
+        pub posts: Vec<Post>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Tag {
+        pub content: String,
+        #[serde(default)]
+        pub count: Option<u64>,
+    }
+}
+
+#[test]
+fn test_parse_search_results() -> Result<(), Box<dyn std::error::Error>> {
+    let results: de::SearchResults = serde_json::from_str(include_str!("../samples/search.json"))?;
+    let results = SearchResults::from(results);
+    assert!(!results.is_empty());
+    assert_eq!(results.projects.len(), 1);
+    assert_eq!(results.projects[0].handle, "example");
+    assert_eq!(
+        results.tags,
+        vec![
+            TagResult {
+                tag: "test post".into(),
+                post_count: Some(1_312),
+            },
+            TagResult {
+                tag: "test posting".into(),
+                post_count: None,
+            },
+        ]
+    );
+    assert_eq!(results.posts.len(), 1);
+    assert_eq!(results.posts[0].headline, "This is a test post.");
+    Ok(())
+}
@@ -1,5 +1,10 @@
-use crate::{Client, Error, Post, PostId};
+use crate::{
+    Ask, BulkOptions, BulkReport, Client, DashboardOptions, Error, IdempotencyKey, Notification,
+    NotificationCursor, Post, PostFilter, PostId, Project, ProjectUpdate,
+};
+use futures::Stream;
 use reqwest::Method;
+use std::collections::HashMap;
 
 /// Logged-in session.
 #[derive(Debug, Clone)]
@@ -34,10 +39,82 @@ impl Session {
             &format!("project/{}/posts", page),
             page,
             None,
+            None,
         )
         .await
     }
 
+    /// Create a post answering an ask sent to `page`.
+    ///
+    /// Returns the new post's ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn answer_ask(
+        &self,
+        page: &str,
+        ask: &Ask,
+        post: &mut Post,
+    ) -> Result<PostId, Error> {
+        let post_id = post
+            .send(
+                self,
+                Method::POST,
+                &format!("project/{}/posts", page),
+                page,
+                None,
+                Some(&ask.ask_id),
+            )
+            .await?;
+        post.ask = Some(ask.clone());
+        Ok(post_id)
+    }
+
+    /// Create the same post on each of several projects, a few at a time.
+    ///
+    /// Attachments are uploaded separately for each project. Failing to create the post on one
+    /// project doesn't stop it from being created on the others; returns the new post's ID, or
+    /// the error, for each project.
+    #[tracing::instrument(skip(self))]
+    pub async fn create_post_multi(
+        &self,
+        projects: &[&str],
+        post: &Post,
+    ) -> HashMap<String, Result<PostId, Error>> {
+        crate::bulk::create_multi(self, projects, post).await
+    }
+
+    /// Create a post, retrying if the request fails in a way that may have happened after the post
+    /// was created.
+    ///
+    /// Up to `max_attempts` attempts are made. Before each retry, the project's recent posts are
+    /// checked for one with the same headline, markdown, and tags (see [`IdempotencyKey`]); if
+    /// one is found, its ID is returned instead of creating a duplicate post.
+    ///
+    /// Returns the new (or existing) post's ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn create_post_idempotent(
+        &self,
+        page: &str,
+        post: &mut Post,
+        max_attempts: u32,
+    ) -> Result<PostId, Error> {
+        crate::idempotency::create_post(self, page, post, max_attempts).await
+    }
+
+    /// Look through a project's recent posts for one matching `key`, published no earlier than
+    /// shortly before `since`.
+    ///
+    /// Drafts aren't matched, since a post with attachments is created as a draft until its
+    /// attachments are uploaded.
+    #[tracing::instrument(skip(self))]
+    pub async fn find_post_by_key(
+        &self,
+        page: &str,
+        key: IdempotencyKey,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<PostId>, Error> {
+        crate::idempotency::find_post(self, page, key, since, None).await
+    }
+
     /// Share a post.
     ///
     /// Returns the new post's ID.
@@ -56,6 +133,7 @@ impl Session {
             &format!("project/{}/posts", page),
             page,
             Some(shared_post),
+            None,
         )
         .await
     }
@@ -76,6 +154,7 @@ impl Session {
             &format!("project/{}/posts/{}", page, id),
             page,
             None,
+            None,
         )
         .await
     }
@@ -90,4 +169,176 @@ impl Session {
             .error_for_status()?;
         Ok(())
     }
+
+    /// Delete every post on a project matching `filter`.
+    ///
+    /// All of the project's posts are read to find matches before any are deleted. Failing to
+    /// delete a post doesn't stop the others from being deleted; check the returned
+    /// [`BulkReport`] for failures.
+    #[tracing::instrument(skip(self))]
+    pub async fn bulk_delete(
+        &self,
+        page: &str,
+        filter: &PostFilter,
+        options: BulkOptions,
+    ) -> Result<BulkReport, Error> {
+        crate::bulk::delete(self, page, filter, options).await
+    }
+
+    /// Edit every post on a project matching `filter`, changing each with `edit`.
+    ///
+    /// All of the project's posts are read to find matches before any are edited. Failing to edit
+    /// a post doesn't stop the others from being edited; check the returned [`BulkReport`] for
+    /// failures. For a dry run, `edit` is not called.
+    #[tracing::instrument(skip(self, edit))]
+    pub async fn bulk_edit(
+        &self,
+        page: &str,
+        filter: &PostFilter,
+        options: BulkOptions,
+        edit: impl FnMut(&mut Post),
+    ) -> Result<BulkReport, Error> {
+        crate::bulk::edit(self, page, filter, options, edit).await
+    }
+
+    /// Update a project's profile.
+    ///
+    /// Only fields of `update` that are set are changed. New avatar and header images are
+    /// uploaded before the profile is updated.
+    #[tracing::instrument(skip(self))]
+    pub async fn update_project(
+        &self,
+        project: &str,
+        update: &mut ProjectUpdate,
+    ) -> Result<(), Error> {
+        update.send(self, project).await
+    }
+
+    /// Follow the project `target` as the project `as_project`.
+    #[tracing::instrument(skip(self))]
+    pub async fn follow(&self, as_project: &str, target: &str) -> Result<(), Error> {
+        self.client
+            .post(&format!("project/{}/following/{}", as_project, target))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Unfollow the project `target` as the project `as_project`.
+    #[tracing::instrument(skip(self))]
+    pub async fn unfollow(&self, as_project: &str, target: &str) -> Result<(), Error> {
+        self.client
+            .delete(&format!("project/{}/following/{}", as_project, target))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Returns a stream of the projects following the given project.
+    ///
+    /// Pages of followers are requested as the stream is polled.
+    pub fn followers<'a>(
+        &'a self,
+        project: &'a str,
+    ) -> impl Stream<Item = Result<Project, Error>> + 'a {
+        crate::page::paginate(move |page| self.get_projects_page(project, "followers", page))
+    }
+
+    /// Returns a stream of the projects the given project follows.
+    ///
+    /// Pages of followed projects are requested as the stream is polled.
+    pub fn following<'a>(
+        &'a self,
+        project: &'a str,
+    ) -> impl Stream<Item = Result<Project, Error>> + 'a {
+        crate::page::paginate(move |page| self.get_projects_page(project, "following", page))
+    }
+
+    /// Get the asks sent to `project` that haven't been answered yet.
+    ///
+    /// Answer one with [`Session::answer_ask`].
+    #[tracing::instrument(skip(self))]
+    pub async fn pending_asks(&self, project: &str) -> Result<Vec<Ask>, Error> {
+        crate::ask::pending(self, project).await
+    }
+
+    /// Get the most recent notifications for the logged-in account, newest first.
+    #[tracing::instrument(skip(self))]
+    pub async fn notifications(&self) -> Result<Vec<Notification>, Error> {
+        crate::notification::get_page(self, 0).await
+    }
+
+    /// Get all notifications after `cursor`, oldest first.
+    ///
+    /// [Advance][`NotificationCursor::advance`] the cursor past each notification you handle to
+    /// read only newer ones next time.
+    #[tracing::instrument(skip(self))]
+    pub async fn notifications_since(
+        &self,
+        cursor: &NotificationCursor,
+    ) -> Result<Vec<Notification>, Error> {
+        crate::notification::since(self, cursor).await
+    }
+
+    /// Returns a stream of new notifications, oldest first, polling cohost every `interval`.
+    ///
+    /// Only notifications after `since` are yielded. If `since` is `None`, only notifications
+    /// created after the first poll are yielded. To resume polling later, keep a
+    /// [`NotificationCursor`] [advanced][`NotificationCursor::advance`] past each notification you
+    /// handle, and pass it as `since`.
+    ///
+    /// Errors are yielded as they happen and polling continues afterward; the stream never ends.
+    #[cfg(feature = "poll")]
+    pub fn poll_notifications(
+        &self,
+        since: Option<NotificationCursor>,
+        interval: std::time::Duration,
+    ) -> impl Stream<Item = Result<Notification, Error>> + '_ {
+        crate::notification::poll(self, since, interval)
+    }
+
+    /// Get a page of posts from the logged-in account's dashboard, the feed of posts from
+    /// followed projects.
+    ///
+    /// Pages start at 0. Once you get an empty page, there are no more pages after that to get;
+    /// they will all be empty.
+    #[tracing::instrument(skip(self))]
+    pub async fn dashboard_page(
+        &self,
+        options: DashboardOptions,
+        page: u64,
+    ) -> Result<Vec<Post>, Error> {
+        options.get_page(self, page).await
+    }
+
+    /// Returns a stream of posts from the logged-in account's dashboard, newest first.
+    ///
+    /// Pages of posts are requested as the stream is polled.
+    pub fn dashboard(
+        &self,
+        options: DashboardOptions,
+    ) -> impl Stream<Item = Result<Post, Error>> + '_ {
+        crate::page::paginate(move |page| options.get_page(self, page))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_projects_page(
+        &self,
+        project: &str,
+        list: &str,
+        page: u64,
+    ) -> Result<Vec<Project>, Error> {
+        let projects_page: crate::project::de::ProjectPage = self
+            .client
+            .get(&format!("project/{}/{}", project, list))
+            .query(&[("page", page.to_string())])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(projects_page.items.into_iter().map(Project::from).collect())
+    }
 }
@@ -0,0 +1,132 @@
+use crate::{Client, Error, Post};
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// A position in a tag feed.
+///
+/// Tag feeds are read backward in time from a reference timestamp, skipping the posts that have
+/// already been read. Because the reference timestamp stays fixed while reading, posts published
+/// after it don't shift the feed, and a stored cursor can be used to resume reading later.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCursor {
+    /// Only posts published before this time are included in the feed.
+    pub ref_timestamp: DateTime<Utc>,
+    /// The number of posts to skip from the start of the feed.
+    pub skip_posts: u64,
+}
+
+impl TagCursor {
+    /// Returns a cursor at the start of the feed as of right now.
+    #[must_use]
+    pub fn now() -> TagCursor {
+        TagCursor {
+            ref_timestamp: Utc::now(),
+            skip_posts: 0,
+        }
+    }
+
+    /// Returns a cursor advanced past `posts` more posts.
+    #[must_use]
+    pub fn skip(self, posts: u64) -> TagCursor {
+        TagCursor {
+            skip_posts: self.skip_posts + posts,
+            ..self
+        }
+    }
+}
+
+/// Options for reading a tag feed with [`Client::tagged_posts`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TagFeedOptions {
+    /// Include posts marked as adult content.
+    pub show_adult_content: bool,
+    /// Hide posts that are shares of other posts.
+    pub hide_shares: bool,
+}
+
+impl TagFeedOptions {
+    pub(crate) async fn get_page(
+        self,
+        client: &Client,
+        tag: &str,
+        cursor: TagCursor,
+    ) -> Result<Vec<Post>, Error> {
+        let posts_page: crate::post::PostPage = client
+            .get(&format!("tagged/{}", encode_path_segment(tag)))
+            .query(&TagFeedQuery {
+                ref_timestamp: cursor.ref_timestamp.timestamp_millis(),
+                skip_posts: cursor.skip_posts,
+                show_18_plus_posts: self.show_adult_content,
+                hide_shares: self.hide_shares,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(posts_page.into())
+    }
+
+    pub(crate) fn stream<'a>(
+        self,
+        client: &'a Client,
+        tag: &'a str,
+        cursor: TagCursor,
+    ) -> impl Stream<Item = Result<Post, Error>> + 'a {
+        futures::stream::try_unfold(Some(cursor), move |cursor| async move {
+            let cursor = match cursor {
+                Some(cursor) => cursor,
+                None => return Ok::<_, Error>(None),
+            };
+            let posts = self.get_page(client, tag, cursor).await?;
+            let next = if posts.is_empty() {
+                None
+            } else {
+                Some(cursor.skip(posts.len().try_into().unwrap_or(u64::MAX)))
+            };
+            Ok(Some((
+                futures::stream::iter(posts.into_iter().map(Ok)),
+                next,
+            )))
+        })
+        .try_flatten()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TagFeedQuery {
+    ref_timestamp: i64,
+    skip_posts: u64,
+    #[serde(rename = "show18PlusPosts")]
+    show_18_plus_posts: bool,
+    hide_shares: bool,
+}
+
+/// Percent-encodes everything in `segment` except unreserved characters, so that tags containing
+/// spaces, slashes, or other special characters can be used in a URL path.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            write!(encoded, "%{:02X}", byte).unwrap();
+        }
+    }
+    encoded
+}
+
+#[test]
+fn test_encode_path_segment() {
+    assert_eq!(encode_path_segment("cohost-bots"), "cohost-bots");
+    assert_eq!(
+        encode_path_segment("the cohost global feed"),
+        "the%20cohost%20global%20feed"
+    );
+    assert_eq!(encode_path_segment("a/b?c#d"), "a%2Fb%3Fc%23d");
+    assert_eq!(encode_path_segment("ほ"), "%E3%81%BB");
+}
@@ -0,0 +1,642 @@
+//! A fake cohost server for integration tests.
+//!
+//! [`TestServer`] listens on a local port and implements enough of cohost's API to log in, create,
+//! read, edit, and delete posts, and upload attachments. Its state is kept in memory and thrown
+//! away when it's dropped.
+//!
+//! ```no_run
+//! use eggbug::{test_server::TestServer, Post};
+//!
+//! # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = TestServer::start()?;
+//! let session = server.session().await?;
+//! let mut post = Post {
+//!     headline: "hello".into(),
+//!     ..Post::default()
+//! };
+//! let id = session.create_post("eggbug", &mut post).await?;
+//! assert_eq!(server.posts("eggbug")[0].headline, "hello");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::post::de;
+use crate::{Client, Error, Post, PostId, Session};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use hyper::header::{CONTENT_TYPE, COOKIE, SET_COOKIE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// The number of posts on each page of a project's posts.
+const PAGE_SIZE: usize = 20;
+
+/// The name of the session cookie set when logging in.
+const SESSION_COOKIE: &str = "connect.sid";
+
+/// A fake cohost server running on a local port. See the [module documentation][self].
+///
+/// The server runs on the Tokio runtime it was started from, and stops when dropped.
+///
+/// Any email and password can log in. Requests that change anything require a logged-in session,
+/// and get a 401 response otherwise.
+#[derive(Debug)]
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    base_url: String,
+    next_id: u64,
+    sessions: HashSet<String>,
+    posts: BTreeMap<PostId, StoredPost>,
+    attachments: HashMap<Uuid, StoredAttachment>,
+}
+
+#[derive(Debug)]
+struct StoredPost {
+    project: String,
+    body: Value,
+    published_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct StoredAttachment {
+    filename: String,
+    upload: Option<Bytes>,
+    url: Option<String>,
+}
+
+impl TestServer {
+    /// Starts a server on a random local port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if not called from within a Tokio runtime.
+    pub fn start() -> Result<TestServer, Error> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State {
+            base_url: format!("http://{}/", addr),
+            ..State::default()
+        }));
+
+        let make_service = make_service_fn({
+            let state = state.clone();
+            move |_| {
+                let state = state.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| handle(state.clone(), request)))
+                }
+            }
+        });
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let server = hyper::Server::from_tcp(listener)
+            .map_err(std::io::Error::other)?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                shutdown_rx.await.ok();
+            });
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                tracing::warn!(%err, "test server failed");
+            }
+        });
+        tracing::info!(%addr, "test server started");
+
+        Ok(TestServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Returns the address the server is listening on.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the base URL to pass to [`Client::with_base_url`].
+    #[must_use]
+    pub fn base_url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// Returns a new client that sends requests to this server.
+    #[must_use]
+    pub fn client(&self) -> Client {
+        Client::new().with_base_url(self.base_url())
+    }
+
+    /// Returns a session logged into this server, without going through
+    /// [`Client::login`]'s deliberately slow password hashing.
+    pub async fn session(&self) -> Result<Session, Error> {
+        let mut client = self.client();
+        client
+            .post("test/session")
+            .send()
+            .await?
+            .error_for_status()?;
+        client.logged_in = true;
+        Ok(Session { client })
+    }
+
+    /// Returns a project's posts as the API would, newest first, including drafts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a request handler panicked while holding the server's state.
+    #[must_use]
+    pub fn posts(&self, project: &str) -> Vec<Post> {
+        let state = self.state.lock().unwrap();
+        state
+            .project_posts(project)
+            .into_iter()
+            .filter_map(|value| serde_json::from_value::<de::Post>(value).ok())
+            .map(Post::from)
+            .collect()
+    }
+
+    /// Returns the body of the upload request for an attachment, if it has been uploaded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a request handler panicked while holding the server's state.
+    #[must_use]
+    pub fn upload(&self, attachment_id: crate::AttachmentId) -> Option<Bytes> {
+        let state = self.state.lock().unwrap();
+        state
+            .attachments
+            .get(&attachment_id.0)
+            .and_then(|attachment| attachment.upload.clone())
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+    };
+    let query = parts.uri.query().unwrap_or_default();
+    let segments = parts
+        .uri
+        .path()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    let logged_in = parts
+        .headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| {
+            cookie
+                .trim()
+                .strip_prefix(SESSION_COOKIE)?
+                .strip_prefix('=')
+        })
+        .any(|id| state.lock().unwrap().sessions.contains(id));
+    tracing::debug!(method = %parts.method, path = %parts.uri.path(), logged_in, "test server");
+
+    let mut state = state.lock().unwrap();
+    let response = match (&parts.method, segments.as_slice()) {
+        (&Method::GET, ["login", "salt"]) => ok_json(&json!({ "salt": "AAAAAAAAAAAAAAAAAAAAAA" })),
+        (&Method::POST, ["login"]) => state.login(&body),
+        (&Method::POST, ["test", "session"]) => state.login(br#"{"email":"","clientHash":""}"#),
+        (&Method::GET, ["project", project, "posts"]) => {
+            let page = query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("page="))
+                .and_then(|page| page.parse().ok())
+                .unwrap_or(0);
+            state.posts_page(project, page)
+        }
+        _ if !logged_in => status(StatusCode::UNAUTHORIZED),
+        (&Method::POST, ["project", project, "posts"]) => state.create_post(project, &body),
+        (&Method::PUT, ["project", project, "posts", id]) => state.edit_post(project, id, &body),
+        (&Method::DELETE, ["project", project, "posts", id]) => state.delete_post(project, id),
+        (&Method::POST, ["trpc", "posts.attachment.start"]) => state.start_attachment(&body),
+        (&Method::POST, ["upload", id]) => state.upload_attachment(id, body),
+        (&Method::POST, ["project", _, "posts", _, "attach", "finish", id]) => {
+            state.finish_attachment(id)
+        }
+        _ => status(StatusCode::NOT_FOUND),
+    };
+    Ok(response)
+}
+
+fn status(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+fn ok_json(value: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(value.to_string()));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+impl State {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn login(&mut self, body: &[u8]) -> Response<Body> {
+        let request: Value = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(_) => return status(StatusCode::BAD_REQUEST),
+        };
+        if !request["email"].is_string() || !request["clientHash"].is_string() {
+            return status(StatusCode::BAD_REQUEST);
+        }
+        let user_id = self.next_id();
+        let session = format!("s{}", user_id);
+        self.sessions.insert(session.clone());
+        let mut response = ok_json(&json!({ "userId": user_id }));
+        response.headers_mut().insert(
+            SET_COOKIE,
+            format!("{}={}; Path=/; HttpOnly", SESSION_COOKIE, session)
+                .parse()
+                .unwrap(),
+        );
+        response
+    }
+
+    fn posts_page(&self, project: &str, page: usize) -> Response<Body> {
+        let posts = self.project_posts(project);
+        let n_items = posts.len();
+        let items = posts
+            .into_iter()
+            .skip(page * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .collect::<Vec<_>>();
+        ok_json(&json!({
+            "nItems": n_items,
+            "nPages": n_items.div_ceil(PAGE_SIZE),
+            "items": items,
+        }))
+    }
+
+    fn project_posts(&self, project: &str) -> Vec<Value> {
+        self.posts
+            .iter()
+            .rev()
+            .filter(|(_, post)| post.project == project)
+            .map(|(id, post)| self.render_post(*id, post))
+            .collect()
+    }
+
+    /// Renders a stored post the way the API returns it.
+    fn render_post(&self, id: PostId, post: &StoredPost) -> Value {
+        let body = &post.body;
+        let blocks = body["blocks"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|block| {
+                let mut block = block.clone();
+                if let Some(attachment) = block.get_mut("attachment") {
+                    let url = attachment["attachmentId"]
+                        .as_str()
+                        .and_then(|id| id.parse().ok())
+                        .and_then(|id| self.attachments.get(&id))
+                        .and_then(|attachment| attachment.url.clone())
+                        .unwrap_or_default();
+                    attachment["fileURL"] = json!(url);
+                    attachment["previewURL"] = json!(url);
+                }
+                block
+            })
+            .collect::<Vec<_>>();
+        let share_tree = body["shareOfPostId"]
+            .as_u64()
+            .map(PostId)
+            .and_then(|shared| Some(self.render_post(shared, self.posts.get(&shared)?)))
+            .into_iter()
+            .collect::<Vec<_>>();
+        let plain_text_body = blocks
+            .iter()
+            .filter_map(|block| block["markdown"]["content"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let filename = format!(
+            "{}-{}",
+            id,
+            slug(body["headline"].as_str().unwrap_or_default())
+        );
+
+        json!({
+            "blocks": blocks,
+            "canShare": true,
+            "commentsLocked": false,
+            "cws": body["cws"],
+            "effectiveAdultContent": body["adultContent"],
+            "filename": filename,
+            "hasAnyContributorMuted": false,
+            "hasCohostPlus": false,
+            "headline": body["headline"],
+            "isLiked": false,
+            "numComments": 0,
+            "numSharedComments": 0,
+            "pinned": false,
+            "plainTextBody": plain_text_body,
+            "postEditUrl": format!("{}{}/post/{}/edit", self.base_url, post.project, id),
+            "postId": id,
+            "postingProject": {
+                "handle": post.project,
+                "displayName": post.project,
+                "dek": "",
+                "description": "",
+                "avatarURL": "",
+                "headerURL": null,
+                "projectId": 1,
+                "privacy": "public",
+            },
+            "publishedAt": post.published_at,
+            "relatedProjects": [],
+            "shareTree": share_tree,
+            "singlePostPageUrl": format!("{}{}/post/{}", self.base_url, post.project, filename),
+            "state": body["postState"],
+            "tags": body["tags"],
+        })
+    }
+
+    fn create_post(&mut self, project: &str, body: &[u8]) -> Response<Body> {
+        let body: Value = match serde_json::from_slice(body) {
+            Ok(body) => body,
+            Err(_) => return status(StatusCode::BAD_REQUEST),
+        };
+        let id = PostId(self.next_id());
+        self.posts.insert(
+            id,
+            StoredPost {
+                project: project.to_owned(),
+                body,
+                published_at: Utc::now(),
+            },
+        );
+        ok_json(&json!({ "postId": id }))
+    }
+
+    fn edit_post(&mut self, project: &str, id: &str, body: &[u8]) -> Response<Body> {
+        let body: Value = match serde_json::from_slice(body) {
+            Ok(body) => body,
+            Err(_) => return status(StatusCode::BAD_REQUEST),
+        };
+        match self.find_post(project, id) {
+            Some((id, post)) => {
+                post.body = body;
+                ok_json(&json!({ "postId": id }))
+            }
+            None => status(StatusCode::NOT_FOUND),
+        }
+    }
+
+    fn delete_post(&mut self, project: &str, id: &str) -> Response<Body> {
+        match self.find_post(project, id) {
+            Some((id, _)) => {
+                self.posts.remove(&id);
+                status(StatusCode::NO_CONTENT)
+            }
+            None => status(StatusCode::NOT_FOUND),
+        }
+    }
+
+    fn find_post(&mut self, project: &str, id: &str) -> Option<(PostId, &mut StoredPost)> {
+        let id = PostId(id.parse().ok()?);
+        let post = self.posts.get_mut(&id)?;
+        (post.project == project).then_some((id, post))
+    }
+
+    fn start_attachment(&mut self, body: &[u8]) -> Response<Body> {
+        let request: Value = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(_) => return status(StatusCode::BAD_REQUEST),
+        };
+        let filename = match request["filename"].as_str() {
+            Some(filename) => filename.to_owned(),
+            None => return status(StatusCode::BAD_REQUEST),
+        };
+        let id = Uuid::from_u128(u128::from(self.next_id()));
+        self.attachments.insert(
+            id,
+            StoredAttachment {
+                filename,
+                upload: None,
+                url: None,
+            },
+        );
+        ok_json(&json!({ "result": { "data": {
+            "attachmentId": id,
+            "url": format!("{}upload/{}", self.base_url, id),
+            "requiredFields": { "key": id.to_string() },
+        } } }))
+    }
+
+    fn upload_attachment(&mut self, id: &str, body: Bytes) -> Response<Body> {
+        match id
+            .parse()
+            .ok()
+            .and_then(|id: Uuid| self.attachments.get_mut(&id))
+        {
+            Some(attachment) => {
+                attachment.upload = Some(body);
+                status(StatusCode::NO_CONTENT)
+            }
+            None => status(StatusCode::NOT_FOUND),
+        }
+    }
+
+    fn finish_attachment(&mut self, id: &str) -> Response<Body> {
+        let base_url = self.base_url.clone();
+        let Some((id, attachment)) = id
+            .parse()
+            .ok()
+            .and_then(|id: Uuid| Some((id, self.attachments.get_mut(&id)?)))
+        else {
+            return status(StatusCode::NOT_FOUND);
+        };
+        if attachment.upload.is_none() {
+            return status(StatusCode::BAD_REQUEST);
+        }
+        let url = format!("{}attachment/{}/{}", base_url, id, attachment.filename);
+        attachment.url = Some(url.clone());
+        ok_json(&json!({ "attachmentId": id, "url": url }))
+    }
+}
+
+fn slug(headline: &str) -> String {
+    let slug = headline
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "post".to_owned()
+    } else {
+        slug
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestServer;
+    use crate::test_util::{png, PNG};
+    use crate::{Attachment, Error, MediaMetadata, Post, Session};
+    use bytes::Bytes;
+
+    #[tokio::test]
+    async fn test_server_posts() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let session = server
+            .client()
+            .login("eggbug@website.invalid", "hunter2")
+            .await?;
+
+        let mut post = Post {
+            headline: "Hello, world!".into(),
+            markdown: "one\n\ntwo".into(),
+            tags: vec!["eggs".into()],
+            ..Post::default()
+        };
+        let id = session.create_post("eggbug", &mut post).await?;
+        let posts = session.as_client().get_posts_page("eggbug", 0).await?;
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].headline, "Hello, world!");
+        assert_eq!(posts[0].markdown, "one\n\ntwo");
+        assert_eq!(posts[0].tags, ["eggs"]);
+        let metadata = posts[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata.locations.id, id);
+        assert!(metadata
+            .locations
+            .url
+            .ends_with("/eggbug/post/2-hello-world"));
+
+        post.headline = "edited".into();
+        session.edit_post("eggbug", id, &mut post).await?;
+        assert_eq!(server.posts("eggbug")[0].headline, "edited");
+        assert!(server.posts("someone-else").is_empty());
+
+        let mut share = Post::default();
+        session.share_post("eggbug", id, &mut share).await?;
+        let posts = server.posts("eggbug");
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].metadata.as_ref().unwrap().share_tree.len(), 1);
+
+        session.delete_post("eggbug", id).await?;
+        assert_eq!(server.posts("eggbug").len(), 1);
+        assert!(session.delete_post("eggbug", id).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_attachment() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let session = server.session().await?;
+
+        let mut post = Post {
+            attachments: vec![png().with_alt_text("an egg".into())],
+            ..Post::default()
+        };
+        session.create_post("eggbug", &mut post).await?;
+        let url = post.attachments[0].url().unwrap().to_owned();
+        assert!(url.starts_with(&server.base_url()));
+        assert!(url.ends_with("/egg.png"));
+
+        let posts = server.posts("eggbug");
+        assert!(!posts[0].draft);
+        assert_eq!(posts[0].attachments[0].url(), Some(url.as_str()));
+        assert_eq!(posts[0].attachments[0].alt_text.as_deref(), Some("an egg"));
+        let upload = server.upload(post.attachments[0].id().unwrap()).unwrap();
+        assert!(upload.windows(PNG.len()).any(|window| window == PNG));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_clone_post() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let session = server.session().await?;
+
+        let template = Post {
+            headline: "twice".into(),
+            attachments: vec![
+                Attachment::new(
+                    b"egg one".to_vec(),
+                    "one.png".into(),
+                    "image/png".into(),
+                    MediaMetadata::Image {
+                        width: None,
+                        height: None,
+                    },
+                ),
+                Attachment::new_from_fn(
+                    || futures::stream::iter([Ok::<_, std::io::Error>(Bytes::from("egg two"))]),
+                    "two.png".into(),
+                    "image/png".into(),
+                    7,
+                    None,
+                ),
+            ],
+            ..Post::default()
+        };
+        for project in ["eggbug", "eggbug2"] {
+            let mut post = template.clone();
+            assert_eq!(post, template);
+            session.create_post(project, &mut post).await?;
+            assert_ne!(post, template);
+            for (attachment, content) in post.attachments.iter().zip([b"egg one", b"egg two"]) {
+                let upload = server.upload(attachment.id().unwrap()).unwrap();
+                assert!(upload.windows(7).any(|window| window == content));
+            }
+        }
+        assert!(template.attachments.iter().all(Attachment::is_new));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_requires_login() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::start()?;
+        let session = Session {
+            client: server.client(),
+        };
+        let mut post = Post {
+            headline: "hello".into(),
+            ..Post::default()
+        };
+        assert!(matches!(
+            session.create_post("eggbug", &mut post).await,
+            Err(Error::Request(err)) if err.status() == Some(reqwest::StatusCode::UNAUTHORIZED)
+        ));
+        Ok(())
+    }
+}
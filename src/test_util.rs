@@ -0,0 +1,74 @@
+//! Helpers shared by the crate's tests.
+
+use crate::{Attachment, Client, MediaMetadata, MemoryTransport, Post, Session};
+use serde_json::json;
+
+/// The ID given to attachments uploaded through [`mock_attachment_upload`].
+pub(crate) const ATTACHMENT_ID: &str = "2b1e7477-ba13-4f7e-9547-f0e2668b92b6";
+
+/// The content of the attachment returned by [`png`].
+pub(crate) const PNG: &[u8] = b"not really a png";
+
+/// Returns a session that sends its requests to `transport`.
+///
+/// This skips logging in, which is slow without optimizations.
+pub(crate) fn session(transport: &MemoryTransport) -> Session {
+    Session {
+        client: Client::new().with_transport(transport.clone()),
+    }
+}
+
+/// Returns a new image attachment named `egg.png`, ready to upload.
+pub(crate) fn png() -> Attachment {
+    Attachment::new(
+        PNG,
+        "egg.png".into(),
+        "image/png".into(),
+        MediaMetadata::Image {
+            width: None,
+            height: None,
+        },
+    )
+}
+
+/// Answers the requests that upload an attachment to post `id` on `project`: starting the upload,
+/// sending the file to the storage bucket, and finishing the upload.
+pub(crate) fn mock_attachment_upload(transport: &MemoryTransport, project: &str, id: u64) {
+    transport.respond_json(
+        "POST",
+        "trpc/posts.attachment.start",
+        200,
+        &json!({ "result": { "data": {
+            "attachmentId": ATTACHMENT_ID,
+            "url": "https://uploads.example.invalid/bucket",
+            "requiredFields": { "key": "value" },
+        } } }),
+    );
+    transport.respond("POST", "bucket", 204, "");
+    transport.respond_json(
+        "POST",
+        &format!(
+            "project/{}/posts/{}/attach/finish/{}",
+            project, id, ATTACHMENT_ID
+        ),
+        200,
+        &json!({ "attachmentId": ATTACHMENT_ID, "url": "https://cdn.example.invalid/egg.png" }),
+    );
+}
+
+/// Returns a page of a project's posts holding only `post`, published just now with ID `id`.
+/// Attachments are left out.
+pub(crate) fn posts_page(id: u64, post: &Post) -> serde_json::Value {
+    let mut page: serde_json::Value =
+        serde_json::from_str(include_str!("../samples/example.project.posts.json")).unwrap();
+    let mut item = page["items"][0].take();
+    item["postId"] = json!(id);
+    item["headline"] = json!(post.headline);
+    item["plainTextBody"] = json!(post.markdown);
+    item["blocks"] = json!([]);
+    item["tags"] = json!(post.tags);
+    item["state"] = json!(u8::from(!post.draft));
+    item["publishedAt"] = json!(chrono::Utc::now());
+    item["shareTree"] = json!([]);
+    json!({ "nItems": 1, "nPages": 1, "items": [item] })
+}
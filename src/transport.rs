@@ -0,0 +1,362 @@
+use crate::Error;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use reqwest::{Method, Request, Response, StatusCode};
+use serde::Serialize;
+use std::fmt::{self, Debug};
+use std::sync::{Arc, Mutex};
+
+/// Sends HTTP requests for a [`Client`][`crate::Client`].
+///
+/// Requests are built with [`reqwest`] and handed to the transport to execute. The default,
+/// [`ReqwestTransport`], sends them over the network; [`MemoryTransport`] answers them with canned
+/// responses so that code built on eggbug can be tested without network access. Set a client's
+/// transport with [`Client::with_transport`][`crate::Client::with_transport`].
+pub trait Transport: Debug + Send + Sync {
+    /// Executes a request, returning its response.
+    ///
+    /// Responses with error status codes should be returned as responses, not errors.
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>>;
+}
+
+/// A [`Transport`] that sends requests over the network with a [`reqwest::Client`].
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// Creates a transport that sends requests with `client`.
+    #[must_use]
+    pub fn new(client: reqwest::Client) -> ReqwestTransport {
+        ReqwestTransport { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        Box::pin(async move { Ok(self.client.execute(request).await?) })
+    }
+}
+
+/// A [`Transport`] that answers requests with canned responses, for tests.
+///
+/// Responses are matched by method and path. A path matches a request if it is the end of the
+/// request URL's path following a `/`, so `project/eggbug/posts` matches a request for
+/// `https://cohost.org/api/v1/project/eggbug/posts?page=0`. Responses added with
+/// [`MemoryTransport::respond_once`] are used once and take priority over those added with
+/// [`MemoryTransport::respond`], which are used for every matching request. Requests that match
+/// nothing get an empty 404 response.
+///
+/// Every request is recorded and can be inspected with [`MemoryTransport::requests`]. Clones
+/// share the same responses and recorded requests, so a clone can be given to a
+/// [`Client`][`crate::Client`] and the original kept for setting up and checking the test.
+///
+/// ```
+/// use eggbug::{Client, MemoryTransport};
+///
+/// # async fn f() -> Result<(), Box<dyn std::error::Error>> {
+/// let transport = MemoryTransport::new();
+/// transport.respond_json(
+///     "GET",
+///     "project/eggbug/posts",
+///     200,
+///     &serde_json::json!({ "nItems": 0, "nPages": 0, "items": [] }),
+/// );
+/// let client = Client::new().with_transport(transport.clone());
+/// assert!(client.get_posts_page("eggbug", 0).await?.is_empty());
+/// assert_eq!(transport.requests().len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTransport {
+    inner: Arc<Mutex<MemoryInner>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryInner {
+    routes: Vec<Route>,
+    requests: Vec<RecordedRequest>,
+}
+
+#[derive(Debug)]
+struct Route {
+    method: Method,
+    path: String,
+    status: StatusCode,
+    body: Bytes,
+    once: bool,
+}
+
+/// A request received by a [`MemoryTransport`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RecordedRequest {
+    /// The request method.
+    pub method: Method,
+    /// The full request URL, including the query string.
+    pub url: String,
+    /// The request body, if it was sent all at once. Streamed bodies, such as attachment uploads,
+    /// are `None`.
+    pub body: Option<Bytes>,
+}
+
+impl RecordedRequest {
+    /// Parses the request body as JSON.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(serde_json::from_slice(
+            self.body.as_deref().unwrap_or_default(),
+        )?)
+    }
+}
+
+impl MemoryTransport {
+    /// Creates a transport with no responses.
+    #[must_use]
+    pub fn new() -> MemoryTransport {
+        MemoryTransport::default()
+    }
+
+    /// Responds to every request matching `method` and `path` with `status` and `body`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `method` isn't a valid HTTP method or `status` isn't a valid status code.
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: impl Into<Bytes>) {
+        self.add_route(method, path, status, body.into(), false);
+    }
+
+    /// Responds to the next request matching `method` and `path` with `status` and `body`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `method` isn't a valid HTTP method or `status` isn't a valid status code.
+    pub fn respond_once(&self, method: &str, path: &str, status: u16, body: impl Into<Bytes>) {
+        self.add_route(method, path, status, body.into(), true);
+    }
+
+    /// Responds to every request matching `method` and `path` with `status` and `body` encoded
+    /// as JSON.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `method` isn't a valid HTTP method, `status` isn't a valid status code, or
+    /// `body` can't be encoded as JSON.
+    pub fn respond_json(&self, method: &str, path: &str, status: u16, body: &impl Serialize) {
+        self.respond(method, path, status, serde_json::to_vec(body).unwrap());
+    }
+
+    /// Responds to the requests made by [`Client::login`][`crate::Client::login`], so that a
+    /// client using this transport can log in with any email and password.
+    pub fn respond_login(&self, user_id: u64) {
+        self.respond_json(
+            "GET",
+            "login/salt",
+            200,
+            &serde_json::json!({ "salt": "AAAAAAAAAAAAAAAAAAAAAA" }),
+        );
+        self.respond_json(
+            "POST",
+            "login",
+            200,
+            &serde_json::json!({ "userId": user_id }),
+        );
+    }
+
+    /// Returns every request received so far, oldest first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread panicked while using this transport.
+    #[must_use]
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.inner.lock().unwrap().requests.clone()
+    }
+
+    fn add_route(&self, method: &str, path: &str, status: u16, body: Bytes, once: bool) {
+        let route = Route {
+            method: method.parse().unwrap(),
+            path: path.trim_start_matches('/').to_owned(),
+            status: StatusCode::from_u16(status).unwrap(),
+            body,
+            once,
+        };
+        self.inner.lock().unwrap().routes.push(route);
+    }
+}
+
+impl Route {
+    fn matches(&self, request: &Request) -> bool {
+        self.method == request.method()
+            && request
+                .url()
+                .path()
+                .strip_suffix(&self.path)
+                .is_some_and(|prefix| prefix.ends_with('/'))
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        let mut inner = self.inner.lock().unwrap();
+        tracing::debug!(method = %request.method(), url = %request.url(), "MemoryTransport");
+        inner.requests.push(RecordedRequest {
+            method: request.method().clone(),
+            url: request.url().to_string(),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(Bytes::copy_from_slice),
+        });
+
+        let index = inner
+            .routes
+            .iter()
+            .position(|route| route.once && route.matches(&request))
+            .or_else(|| {
+                inner
+                    .routes
+                    .iter()
+                    .position(|route| route.matches(&request))
+            });
+        let (status, body) = match index {
+            Some(index) if inner.routes[index].once => {
+                let route = inner.routes.remove(index);
+                (route.status, route.body)
+            }
+            Some(index) => (inner.routes[index].status, inner.routes[index].body.clone()),
+            None => (StatusCode::NOT_FOUND, Bytes::new()),
+        };
+        drop(inner);
+
+        let mut response = http::Response::new(body);
+        *response.status_mut() = status;
+        Box::pin(futures::future::ready(Ok(Response::from(response))))
+    }
+}
+
+/// A request being built, which is sent with the [`Client`][`crate::Client`]'s transport.
+pub(crate) struct RequestBuilder {
+    transport: Arc<dyn Transport>,
+    inner: reqwest::RequestBuilder,
+}
+
+impl RequestBuilder {
+    pub(crate) fn new(transport: Arc<dyn Transport>, inner: reqwest::RequestBuilder) -> Self {
+        RequestBuilder { transport, inner }
+    }
+
+    pub(crate) fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        RequestBuilder {
+            inner: self.inner.query(query),
+            ..self
+        }
+    }
+
+    pub(crate) fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        RequestBuilder {
+            inner: self.inner.json(json),
+            ..self
+        }
+    }
+
+    pub(crate) fn multipart(self, form: reqwest::multipart::Form) -> Self {
+        RequestBuilder {
+            inner: self.inner.multipart(form),
+            ..self
+        }
+    }
+
+    pub(crate) async fn send(self) -> Result<Response, Error> {
+        let request = self.inner.build()?;
+        self.transport.execute(request).await
+    }
+}
+
+impl Debug for RequestBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryTransport;
+    use crate::test_util::{mock_attachment_upload, png, session, ATTACHMENT_ID};
+    use crate::{Error, Post, PostId};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_memory_transport_create_post() -> Result<(), Box<dyn std::error::Error>> {
+        let transport = MemoryTransport::new();
+        transport.respond_json("POST", "project/eggbug/posts", 200, &json!({ "postId": 1 }));
+        transport.respond_once("PUT", "project/eggbug/posts/1", 500, "oops");
+        transport.respond_json(
+            "PUT",
+            "project/eggbug/posts/1",
+            200,
+            &json!({ "postId": 1 }),
+        );
+
+        let session = session(&transport);
+        let mut post = Post {
+            headline: "hello".into(),
+            ..Post::default()
+        };
+        assert_eq!(session.create_post("eggbug", &mut post).await?, PostId(1));
+        assert!(matches!(
+            session.edit_post("eggbug", PostId(1), &mut post).await,
+            Err(Error::Request(err)) if err.status().map(|s| s.as_u16()) == Some(500)
+        ));
+        session.edit_post("eggbug", PostId(1), &mut post).await?;
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            requests[0].url,
+            "https://cohost.org/api/v1/project/eggbug/posts"
+        );
+        let body: serde_json::Value = requests[0].json()?;
+        assert_eq!(body["headline"], "hello");
+        assert_eq!(body["postState"], 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_transport_attachment() -> Result<(), Box<dyn std::error::Error>> {
+        let transport = MemoryTransport::new();
+        transport.respond_json("POST", "project/eggbug/posts", 200, &json!({ "postId": 1 }));
+        mock_attachment_upload(&transport, "eggbug", 1);
+        transport.respond_json(
+            "PUT",
+            "project/eggbug/posts/1",
+            200,
+            &json!({ "postId": 1 }),
+        );
+
+        let session = session(&transport);
+        let mut post = Post {
+            attachments: vec![png()],
+            ..Post::default()
+        };
+        session.create_post("eggbug", &mut post).await?;
+        assert_eq!(
+            post.attachments[0].url(),
+            Some("https://cdn.example.invalid/egg.png")
+        );
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 5);
+        assert_eq!(requests[0].json::<serde_json::Value>()?["postState"], 0);
+        assert_eq!(requests[2].url, "https://uploads.example.invalid/bucket");
+        let body: serde_json::Value = requests[4].json()?;
+        assert_eq!(body["postState"], 1);
+        assert_eq!(
+            body["blocks"][0]["attachment"]["attachmentId"],
+            ATTACHMENT_ID
+        );
+        Ok(())
+    }
+}